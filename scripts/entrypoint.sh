@@ -6,7 +6,7 @@ DEPLOY_KEY="/run/secrets/links_deploy_key"
 
 # LINKS_REQUIRED gates the fail-loud behavior. Prod MUST set LINKS_REQUIRED=1
 # (see the container-cluster compose): the real links repo must be cloned into
-# /app, and if the deploy key is missing OR the git fetch/checkout fails we
+# /app, and if the deploy key is missing OR the git fetch fails we
 # exit non-zero and crash-loop instead of silently serving the bundled STUB
 # links.yaml (foo/bar/test). That stub-serving failure took down all ~167 real
 # jrj.io short-links once (ops#138) precisely because it was silent.
@@ -30,28 +30,20 @@ if [ -f "$DEPLOY_KEY" ]; then
   ssh-keyscan -t ed25519 github.com >> /root/.ssh/known_hosts 2>/dev/null || true
   export GIT_SSH_COMMAND="ssh -i /root/.ssh/id_ed25519 -o IdentitiesOnly=yes -o StrictHostKeyChecking=no"
 
-  # Clone the links repo into /app if not already a git repo.
-  # This makes /app the redirective-links repo so `git pull` (webhook reload) works.
+  # Initialize /app/.git as a BARE links repository if not already a git repo.
+  # redirective fetches into it and reads links.yaml straight out of
+  # origin/main's tree (see [git] in redirective.toml), so nothing is ever
+  # checked out: no dirty working tree, no --ff-only failures, and the bundled
+  # stub links.yaml next to it is left alone.
   if [ ! -d /app/.git ]; then
-    echo "Initializing git repo for links..."
-    cd /app
-    git init -q
-    git remote add origin "$LINKS_REPO"
-    if git fetch origin main; then
-      # The bundled stub links.yaml is untracked and would block the checkout;
-      # stash a copy so we can restore it if checkout unexpectedly fails in dev.
-      cp links.yaml /tmp/links.stub.yaml 2>/dev/null || true
-      rm -f links.yaml
-      if git checkout -b main origin/main; then
-        echo "Links repository initialized from $LINKS_REPO."
-      elif [ "$LINKS_REQUIRED" = "1" ]; then
-        fail "'git checkout origin/main' failed"
-      else
-        echo "WARN: checkout failed; restoring bundled stub links.yaml (LINKS_REQUIRED!=1)." >&2
-        cp /tmp/links.stub.yaml links.yaml 2>/dev/null || true
-      fi
+    echo "Initializing bare git repo for links..."
+    git init -q --bare /app/.git
+    git --git-dir=/app/.git remote add origin "$LINKS_REPO"
+    if git --git-dir=/app/.git fetch -q origin main \
+      && git --git-dir=/app/.git cat-file -e origin/main:links.yaml; then
+      echo "Links repository initialized from $LINKS_REPO."
     elif [ "$LINKS_REQUIRED" = "1" ]; then
-      fail "'git fetch origin main' failed"
+      fail "'git fetch origin main' failed or origin/main has no links.yaml"
     else
       echo "WARN: git fetch failed; keeping bundled stub links.yaml (LINKS_REQUIRED!=1)." >&2
     fi
@@ -0,0 +1,580 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! api module: authenticated write API for the link table. Each change is
+//! validated against the current table, written into links.yaml (keeping its
+//! comments and order), committed to the links repo and pushed, then served.
+
+use crate::config::{ApiConfig, GitConfig, LinkFormat, ReloadConfig, ReloadMode, parse_links};
+use crate::errors::Error;
+use crate::git::{self, GIT_BINARY};
+use crate::payload::tracked_branch;
+use crate::relay::RelayOutbox;
+use crate::reload::ReloadCoordinator;
+use crate::signature;
+use crate::yaml_edit::LinksDocument;
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Extension, Path},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Route of a single link.
+pub const LINK_ROUTE: &str = "/api/links/:code";
+
+/// State shared by the API handlers.
+#[derive(Clone)]
+struct ApiState {
+    coordinator: ReloadCoordinator,
+    git: GitConfig,
+    api: ApiConfig,
+    token: String,
+    outbox: RelayOutbox,
+    /// Peers to relay each pushed change to, as a webhook would be.
+    relay_targets: Vec<String>,
+}
+
+/// Request body of `POST` and `PUT`.
+#[derive(Deserialize)]
+struct LinkBody {
+    url: String,
+}
+
+/// Response to a successful change.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct LinkChange {
+    pub code: String,
+    /// The link's target after the change (`None` once deleted).
+    pub url: Option<String>,
+    /// Commit the change was pushed as.
+    pub commit: String,
+    /// Whether that commit is being served. False if the reload after the
+    /// push failed: the change is in the repo all the same, and is served
+    /// once a later reload succeeds.
+    pub reloaded: bool,
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody {
+    error: String,
+}
+
+/// A request that can't be applied, and the status to answer with.
+struct ApiError(StatusCode, String);
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        let status = match e {
+            Error::Config(_) | Error::YamlParse(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::UntrustedCommit { .. } => StatusCode::CONFLICT,
+            Error::Git(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(ApiErrorBody { error: self.1 })).into_response()
+    }
+}
+
+enum Change {
+    /// `POST`: add a code that doesn't exist yet.
+    Create(String),
+    /// `PUT`: add or replace a code.
+    Upsert(String),
+    /// `DELETE`: remove an existing code.
+    Delete,
+}
+
+/// Refuse a config that enables the API over a links file it can't edit:
+/// changes are written as YAML, so in git mode the file must resolve to the
+/// YAML format (via `reload.format` or its extension).
+pub fn check_format(git: &GitConfig, reload: &ReloadConfig, api: &ApiConfig) -> Result<(), Error> {
+    let enabled = api.token.as_deref().is_some_and(|t| !t.is_empty());
+    let format = LinkFormat::resolve(reload.format, &git.links_path);
+    if enabled && reload.mode == ReloadMode::Git && format != LinkFormat::Yaml {
+        return Err(Error::Config(format!(
+            "the write API only edits YAML links files, but {} is read as {:?}; \
+             unset api.token or use a YAML links file",
+            git.links_path, format
+        )));
+    }
+    Ok(())
+}
+
+/// The API routes, or `None` when no token is configured. Writes need git
+/// mode; a signing policy would refuse the API's unsigned commits, so the API
+/// is unavailable with `trusted_signing_keys` set. Each pushed change is
+/// relayed to `relay_targets` through `outbox` once served.
+pub fn routes(
+    coordinator: ReloadCoordinator,
+    git: &GitConfig,
+    api: &ApiConfig,
+    outbox: RelayOutbox,
+    relay_targets: Vec<String>,
+) -> Option<Router<()>> {
+    let token = api.token.clone().filter(|t| !t.is_empty())?;
+    if !git.trusted_signing_keys.is_empty() {
+        tracing::warn!("write API disabled: its commits would fail the signing policy");
+        return None;
+    }
+    let state = ApiState {
+        coordinator,
+        git: git.clone(),
+        api: api.clone(),
+        token,
+        outbox,
+        relay_targets,
+    };
+    Some(
+        Router::new()
+            .route(
+                LINK_ROUTE,
+                post(create_handler)
+                    .put(upsert_handler)
+                    .delete(delete_handler),
+            )
+            .layer(Extension(state)),
+    )
+}
+
+async fn create_handler(
+    Extension(state): Extension<ApiState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    respond(&state, &headers, code, || {
+        Ok(Change::Create(parse_body::<LinkBody>(&body)?.url))
+    })
+    .await
+}
+
+async fn upsert_handler(
+    Extension(state): Extension<ApiState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    respond(&state, &headers, code, || {
+        Ok(Change::Upsert(parse_body::<LinkBody>(&body)?.url))
+    })
+    .await
+}
+
+async fn delete_handler(
+    Extension(state): Extension<ApiState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    respond(&state, &headers, code, || Ok(Change::Delete)).await
+}
+
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("invalid body: {}", e)))
+}
+
+/// Authenticate, then build the change (parsing the body) and apply it.
+async fn respond(
+    state: &ApiState,
+    headers: &HeaderMap,
+    code: String,
+    change: impl FnOnce() -> Result<Change, ApiError>,
+) -> Response {
+    if !signature::verify_bearer(&state.token, headers) {
+        return ApiError(StatusCode::UNAUTHORIZED, "missing or invalid token".into())
+            .into_response();
+    }
+    let result = match change() {
+        Ok(change) => apply(state, &code, change).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok((status, change)) => (status, Json(change)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Codes are single URL path segments.
+fn validate_code(code: &str) -> Result<(), ApiError> {
+    if code.is_empty() || code.contains('/') || code.contains(char::is_whitespace) {
+        return Err(ApiError(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("invalid code '{}'", code),
+        ));
+    }
+    Ok(())
+}
+
+/// Apply `change` to the tip of the links branch: under the reload lock,
+/// fetch, edit, commit, push, then reload so the commit is served and relay
+/// it to the peers. Once pushed, the change is reported as made even if the
+/// reload fails, so a client doesn't retry it.
+async fn apply(
+    state: &ApiState,
+    code: &str,
+    change: Change,
+) -> Result<(StatusCode, LinkChange), ApiError> {
+    validate_code(code)?;
+    let git = &state.git;
+    let branch = tracked_branch(git).ok_or_else(|| {
+        ApiError(
+            StatusCode::CONFLICT,
+            format!("git ref '{}' is not a branch; cannot push", git.git_ref),
+        )
+    })?;
+    let _guard = state.coordinator.lock().await;
+    git::fetch(GIT_BINARY, git)?;
+    let (parent, content) = git::read_links_at_ref(GIT_BINARY, git)?;
+    let mut links = parse_links(&content)?;
+    let mut doc = LinksDocument::parse(&content)?;
+    let (status, message, url) = match change {
+        Change::Create(_) if links.contains_key(code) => {
+            return Err(ApiError(
+                StatusCode::CONFLICT,
+                format!("code '{}' already exists", code),
+            ));
+        }
+        Change::Delete if !links.contains_key(code) => {
+            return Err(ApiError(
+                StatusCode::NOT_FOUND,
+                format!("code '{}' does not exist", code),
+            ));
+        }
+        Change::Create(url) => {
+            links.insert(code.to_string(), url.clone());
+            doc.insert(code, &url)?;
+            (StatusCode::CREATED, format!("Add link {}", code), Some(url))
+        }
+        Change::Upsert(url) if links.get(code) == Some(&url) => {
+            // Nothing to commit.
+            let change = LinkChange {
+                code: code.to_string(),
+                url: Some(url),
+                commit: parent,
+                reloaded: true,
+            };
+            return Ok((StatusCode::OK, change));
+        }
+        Change::Upsert(url) => {
+            let (status, verb) = match links.insert(code.to_string(), url.clone()) {
+                None => (StatusCode::CREATED, "Add"),
+                Some(_) => (StatusCode::OK, "Update"),
+            };
+            doc.set(code, &url)?;
+            (status, format!("{} link {}", verb, code), Some(url))
+        }
+        Change::Delete => {
+            links.remove(code);
+            doc.remove(code)?;
+            (StatusCode::OK, format!("Remove link {}", code), None)
+        }
+    };
+    let edited = doc.to_string();
+    // The edited file must validate and say exactly what we meant it to.
+    if parse_links(&edited)? != links {
+        return Err(ApiError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "edited links file does not match the requested change".into(),
+        ));
+    }
+    let commit = git::commit_links(
+        GIT_BINARY,
+        git,
+        &parent,
+        &edited,
+        &message,
+        &state.api.author_name,
+        &state.api.author_email,
+    )?;
+    git::push(GIT_BINARY, git, &commit, &branch)?;
+    tracing::info!(code = %code, commit = %commit, "{}", message);
+    let reloaded = match state.coordinator.reloader().reload().await {
+        Ok(_) => {
+            for peer_url in &state.relay_targets {
+                state.outbox.enqueue(peer_url.clone());
+            }
+            true
+        }
+        Err(e) => {
+            tracing::warn!(commit = %commit, error = %e, "change pushed but reload failed");
+            false
+        }
+    };
+    let change = LinkChange {
+        code: code.to_string(),
+        url,
+        commit,
+        reloaded,
+    };
+    Ok((status, change))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::RouterCache;
+    use crate::metrics::init_metrics;
+    use crate::relay::{Delivery, RelaySecrets};
+    use crate::reload::testing::GitFixture;
+    use crate::reload::{ReloadStatus, Reloader};
+    use crate::source::GitSource;
+    use axum::body::Body;
+    use axum::http::Request;
+    use hyper::body::to_bytes;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::Mutex as TokioMutex;
+    use tower::ServiceExt;
+
+    const LINKS: &str =
+        "# team links\nfoo: https://example.com # keep me\n\nbar: https://example.org\n";
+
+    fn app(fixture: &GitFixture) -> (Router<()>, RouterCache) {
+        let cache = RouterCache::new(HashMap::new());
+        let reloader = fixture.reloader(cache.clone(), init_metrics());
+        let (app, _) = app_with(fixture, reloader, Vec::new());
+        (app, cache)
+    }
+
+    /// The API over `fixture`'s repo, serving through `reloader` and relaying
+    /// to `relay_targets`; also returns the relay outbox.
+    fn app_with(
+        fixture: &GitFixture,
+        reloader: Reloader,
+        relay_targets: Vec<String>,
+    ) -> (Router<()>, RelayOutbox) {
+        let outbox = RelayOutbox::new(
+            RelaySecrets::default(),
+            Delivery::Webhook,
+            reloader.metrics.clone(),
+            Duration::from_secs(60),
+            None,
+        );
+        let coordinator = ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(())));
+        let api = ApiConfig {
+            token: Some("s3cret".to_string()),
+            author_name: "Link Bot".to_string(),
+            author_email: "links@example.com".to_string(),
+        };
+        let routes = routes(
+            coordinator,
+            &fixture.git,
+            &api,
+            outbox.clone(),
+            relay_targets,
+        );
+        (routes.unwrap(), outbox)
+    }
+
+    #[test]
+    fn test_check_format_refuses_non_yaml_links_file() {
+        let mut git = GitConfig::default();
+        let mut reload = ReloadConfig::default();
+        let mut api = ApiConfig::default();
+        git.links_path = "links.json".to_string();
+        assert!(check_format(&git, &reload, &api).is_ok());
+        api.token = Some("s3cret".to_string());
+        assert!(matches!(
+            check_format(&git, &reload, &api),
+            Err(Error::Config(msg)) if msg.contains("links.json")
+        ));
+        reload.format = Some(LinkFormat::Yaml);
+        assert!(check_format(&git, &reload, &api).is_ok());
+        git.links_path = "links.yaml".to_string();
+        reload.format = Some(LinkFormat::Csv);
+        assert!(check_format(&git, &reload, &api).is_err());
+        // Outside git mode the API is off anyway.
+        reload.mode = ReloadMode::Watch;
+        assert!(check_format(&git, &reload, &api).is_ok());
+    }
+
+    fn request(method: &str, code: &str, token: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(format!("/api/links/{}", code))
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn origin_file(fixture: &GitFixture) -> String {
+        let output = std::process::Command::new(GIT_BINARY)
+            .current_dir(fixture.root.join("origin"))
+            .args(["show", "main:links.yaml"])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_create_update_delete_commit_and_serve() {
+        let fixture = GitFixture::new(LINKS);
+        fixture.accept_pushes();
+        let (app, cache) = app(&fixture);
+
+        let resp = app
+            .clone()
+            .oneshot(request(
+                "POST",
+                "new",
+                "s3cret",
+                r#"{"url": "https://new.example"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let change: LinkChange =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(change.url.as_deref(), Some("https://new.example"));
+        assert!(change.reloaded);
+        assert_eq!(cache.lookup("new"), Some("https://new.example".to_string()));
+        assert_eq!(
+            origin_file(&fixture),
+            format!("{}new: https://new.example\n", LINKS)
+        );
+
+        let resp = app
+            .clone()
+            .oneshot(request(
+                "PUT",
+                "foo",
+                "s3cret",
+                r#"{"url": "https://foo.example"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(origin_file(&fixture).contains("foo: https://foo.example # keep me\n"));
+
+        let resp = app
+            .clone()
+            .oneshot(request("DELETE", "bar", "s3cret", ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(cache.lookup("bar"), None);
+        assert_eq!(
+            origin_file(&fixture),
+            "# team links\nfoo: https://foo.example # keep me\n\nnew: https://new.example\n"
+        );
+
+        let log = std::process::Command::new(GIT_BINARY)
+            .current_dir(fixture.root.join("origin"))
+            .args(["log", "-1", "--format=%an <%ae> %s", "main"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(log.stdout).unwrap().trim(),
+            "Link Bot <links@example.com> Remove link bar"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_changes_leave_repo_alone() {
+        let fixture = GitFixture::new(LINKS);
+        fixture.accept_pushes();
+        let (app, _) = app(&fixture);
+        let cases = [
+            request("POST", "foo", "wrong", r#"{"url": "https://x"}"#),
+            request("POST", "foo", "s3cret", r#"{"url": "https://x"}"#),
+            request("DELETE", "missing", "s3cret", ""),
+            request("PUT", "foo", "s3cret", r#"{"url": " "}"#),
+            request("PUT", "foo", "s3cret", "not json"),
+        ];
+        let expected = [
+            StatusCode::UNAUTHORIZED,
+            StatusCode::CONFLICT,
+            StatusCode::NOT_FOUND,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            StatusCode::BAD_REQUEST,
+        ];
+        for (req, status) in cases.into_iter().zip(expected) {
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), status);
+        }
+        assert_eq!(origin_file(&fixture), LINKS);
+    }
+
+    #[tokio::test]
+    async fn test_pushed_change_is_relayed_to_peers() {
+        let fixture = GitFixture::new(LINKS);
+        fixture.accept_pushes();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), init_metrics());
+        // Nothing listens there, so the relay stays pending.
+        let peer = "http://127.0.0.1:9/git-webhook".to_string();
+        let (app, outbox) = app_with(&fixture, reloader, vec![peer]);
+        let resp = app
+            .oneshot(request("DELETE", "bar", "s3cret", ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(outbox.depth(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_after_push_still_reports_the_change() {
+        let fixture = GitFixture::new(LINKS);
+        fixture.accept_pushes();
+        let metrics = init_metrics();
+        let status = ReloadStatus::new(metrics.clone(), 0, None);
+        // /usr/bin/false stands in for a git fetch that fails.
+        let source =
+            GitSource::new(fixture.git.clone(), LinkFormat::Yaml).with_git_binary("/usr/bin/false");
+        let reloader = Reloader::new(
+            RouterCache::new(HashMap::new()),
+            metrics,
+            status,
+            Arc::new(source),
+        );
+        let peer = "http://127.0.0.1:9/git-webhook".to_string();
+        let (app, outbox) = app_with(&fixture, reloader, vec![peer]);
+        let resp = app
+            .oneshot(request("DELETE", "bar", "s3cret", ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let change: LinkChange =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert!(!change.reloaded);
+        assert!(!origin_file(&fixture).contains("bar:"));
+        assert_eq!(outbox.depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_api_disabled_without_token_or_with_signing_policy() {
+        let fixture = GitFixture::new(LINKS);
+        let coordinator = ReloadCoordinator::new(
+            fixture.reloader(RouterCache::new(HashMap::new()), init_metrics()),
+            Arc::new(TokioMutex::new(())),
+        );
+        let outbox = RelayOutbox::new(
+            RelaySecrets::default(),
+            Delivery::Webhook,
+            init_metrics(),
+            Duration::ZERO,
+            None,
+        );
+        let disabled = |git: &GitConfig, api: &ApiConfig| {
+            routes(coordinator.clone(), git, api, outbox.clone(), Vec::new()).is_none()
+        };
+        assert!(disabled(&fixture.git, &ApiConfig::default()));
+        let signed = GitConfig {
+            trusted_signing_keys: vec!["SHA256:x".to_string()],
+            ..fixture.git.clone()
+        };
+        let api = ApiConfig {
+            token: Some("s3cret".to_string()),
+            ..ApiConfig::default()
+        };
+        assert!(disabled(&signed, &api));
+    }
+}
@@ -0,0 +1,66 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! backoff module: capped exponential backoff with jitter for retried work
+//! (failing git polls), so a fleet doesn't retry in lockstep.
+
+use std::time::Duration;
+
+/// Exponential backoff schedule: `base * 2^failures`, capped at `max`.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Backoff {
+            base,
+            max: max.max(base),
+        }
+    }
+
+    /// Un-jittered delay after `failures` consecutive failures; `0` gives
+    /// `base`.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 1u32.checked_shl(failures.min(31)).unwrap_or(u32::MAX);
+        self.base.saturating_mul(factor).min(self.max)
+    }
+
+    /// `delay(failures)` with "equal jitter": a uniformly random point in
+    /// its upper half. Never shorter than half the delay, so retries still
+    /// back off, but spread out across nodes.
+    pub fn jittered(&self, failures: u32) -> Duration {
+        let delay = self.delay(failures);
+        let half = delay / 2;
+        half + (delay - half).mul_f64(fastrand::f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_up_to_cap() {
+        let backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(600));
+        let delays: Vec<u64> = (0..6).map(|n| backoff.delay(n).as_secs()).collect();
+        assert_eq!(delays, [60, 120, 240, 480, 600, 600]);
+        // Huge failure counts saturate rather than overflow.
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_jitter_stays_in_upper_half() {
+        let backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(600));
+        for _ in 0..100 {
+            let delay = backoff.jittered(1);
+            assert!(delay >= Duration::from_secs(60) && delay <= Duration::from_secs(120));
+        }
+    }
+
+    #[test]
+    fn test_cap_never_below_base() {
+        let backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(10));
+        assert_eq!(backoff.delay(3), Duration::from_secs(60));
+    }
+}
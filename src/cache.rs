@@ -26,6 +26,11 @@ impl RouterCache {
         map_arc.get(code).cloned()
     }
 
+    /// Current mapping, as a cheap shared snapshot.
+    pub fn snapshot(&self) -> Arc<HashMap<String, String>> {
+        self.inner.load_full()
+    }
+
     /// Atomically swap in a new mapping.
     pub fn swap(&self, new_map: HashMap<String, String>) {
         self.inner.store(Arc::new(new_map));
@@ -0,0 +1,11 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! client module: the outbound HTTP(S) client shared by peer relays, cluster
+//! queries and remote link sources.
+
+/// Client for outbound HTTP(S) requests.
+pub type HttpsClient = hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>;
+
+/// Build the outbound client (TLS via the platform's native-tls).
+pub fn https_client() -> HttpsClient {
+    hyper::Client::builder().build(hyper_tls::HttpsConnector::new())
+}
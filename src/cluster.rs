@@ -0,0 +1,702 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! cluster module: compares the link table this node serves with its peers',
+//! so a fleet that has drifted apart (a missed relay, a peer stuck on failing
+//! reloads) shows up on `/cluster/status` and in the divergence gauges; and
+//! receives tables pushed by peers in snapshot sync mode.
+
+use crate::client::https_client;
+use crate::reload::{ReloadCoordinator, Reloader, unix_now};
+use crate::signature::{self, RelayStamp};
+use crate::source::LinkSnapshot;
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::Extension,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Mutex as TokioMutex;
+use tokio::task;
+
+/// Route reporting this node's own table (queried by its peers).
+pub const NODE_ROUTE: &str = "/cluster/node";
+/// Route comparing this node's table with every peer's.
+pub const STATUS_ROUTE: &str = "/cluster/status";
+/// Route peers push their table to in snapshot sync mode.
+pub const SNAPSHOT_ROUTE: &str = "/cluster/snapshot";
+
+/// Timeout for querying one peer.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SHA-256 of a link table, hex-encoded. Entries are hashed in code order,
+/// so equal tables hash equal whatever their source format or order.
+pub fn table_hash(links: &HashMap<String, String>) -> String {
+    let mut entries: Vec<_> = links.iter().collect();
+    entries.sort();
+    let mut hasher = Sha256::new();
+    for (code, url) in entries {
+        hasher.update(code.as_bytes());
+        hasher.update([0]);
+        hasher.update(url.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// The table a node is serving, as reported on `/cluster/node`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct NodeState {
+    /// `table_hash` of the served table.
+    pub hash: String,
+    /// Git commit the table came from, if known.
+    pub head: Option<String>,
+    pub link_count: usize,
+    /// The node's ID, if it takes part in leader election.
+    #[serde(default)]
+    pub node_id: Option<String>,
+}
+
+impl NodeState {
+    /// State of the table `reloader` is currently serving.
+    pub fn current(reloader: &Reloader, node_id: Option<&str>) -> Self {
+        let links = reloader.cache.snapshot();
+        NodeState {
+            hash: table_hash(&links),
+            head: reloader.status.report().head,
+            link_count: links.len(),
+            node_id: node_id.map(str::to_string),
+        }
+    }
+}
+
+/// A link table as pushed to peers in snapshot sync mode.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct TableSnapshot {
+    pub links: HashMap<String, String>,
+    /// Git commit the table came from, if known.
+    pub head: Option<String>,
+    /// `table_hash` of `links`, checked on receipt.
+    pub hash: String,
+}
+
+impl TableSnapshot {
+    /// The table `reloader` is currently serving.
+    pub fn current(reloader: &Reloader) -> Self {
+        let links = (*reloader.cache.snapshot()).clone();
+        TableSnapshot {
+            hash: table_hash(&links),
+            head: reloader.status.report().head,
+            links,
+        }
+    }
+}
+
+/// One peer's entry on `/cluster/status`.
+#[derive(Debug, Serialize)]
+pub struct PeerState {
+    /// The peer's `/cluster/node` URL.
+    pub url: String,
+    /// What the peer reported, if it could be reached.
+    pub state: Option<NodeState>,
+    pub error: Option<String>,
+    /// Whether the peer serves the same table as this node. An unreachable
+    /// peer is not counted as diverged.
+    pub in_sync: bool,
+}
+
+/// Body of `/cluster/status`.
+#[derive(Debug, Serialize)]
+pub struct ClusterReport {
+    pub node: NodeState,
+    pub peers: Vec<PeerState>,
+    /// Every reachable peer serves the same table as this node.
+    pub consistent: bool,
+    /// Unix time nodes were first seen disagreeing, while they still do.
+    pub diverged_since_unix: Option<u64>,
+    /// The disagreement has lasted longer than the threshold.
+    pub diverged: bool,
+}
+
+/// The URL of `route` on the host of a peer's webhook URL, or `None` if
+/// `peer_url` isn't an absolute URL.
+pub fn peer_route(peer_url: &str, route: &str) -> Option<String> {
+    let uri: hyper::Uri = peer_url.parse().ok()?;
+    Some(format!(
+        "{}://{}{}",
+        uri.scheme_str()?,
+        uri.authority()?,
+        route
+    ))
+}
+
+/// Compares this node's table with its peers' and tracks how long they have
+/// disagreed, mirrored into the cluster gauges.
+#[derive(Clone)]
+pub struct ClusterMonitor {
+    reloader: Reloader,
+    /// Reported on `/cluster/node` for the peers' leader election.
+    node_id: Option<String>,
+    /// The peers' `/cluster/node` URLs.
+    peers: Vec<String>,
+    threshold: Duration,
+    diverged_since: Arc<Mutex<Option<u64>>>,
+}
+
+impl ClusterMonitor {
+    /// Monitor the peers behind `peer_urls` (webhook URLs; their
+    /// `/cluster/node` is queried on the same host). `node_id` is this
+    /// node's election ID, if it takes part in leader election.
+    pub fn new(
+        reloader: Reloader,
+        node_id: Option<String>,
+        peer_urls: &[String],
+        threshold: Duration,
+    ) -> Self {
+        let peers = peer_urls
+            .iter()
+            .filter_map(|url| {
+                let node = peer_route(url, NODE_ROUTE);
+                if node.is_none() {
+                    tracing::warn!(peer = %url, "peer URL has no host; not monitored");
+                }
+                node
+            })
+            .collect();
+        ClusterMonitor {
+            reloader,
+            node_id,
+            peers,
+            threshold,
+            diverged_since: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Query every peer concurrently and compare their tables with ours.
+    pub async fn check(&self) -> ClusterReport {
+        let queries: Vec<_> = self
+            .peers
+            .iter()
+            .map(|url| {
+                let url = url.clone();
+                task::spawn(async move {
+                    let result = fetch_node_state(&url).await;
+                    (url, result)
+                })
+            })
+            .collect();
+        let mut results = Vec::with_capacity(queries.len());
+        for query in queries {
+            results.push(query.await.expect("peer query task panicked"));
+        }
+        // Read our own state after the peers answer, so a reload that
+        // landed meanwhile is compared with what they report now.
+        let node = NodeState::current(&self.reloader, self.node_id.as_deref());
+        let peers: Vec<PeerState> = results
+            .into_iter()
+            .map(|(url, result)| match result {
+                Ok(state) => PeerState {
+                    in_sync: state.hash == node.hash,
+                    url,
+                    state: Some(state),
+                    error: None,
+                },
+                Err(error) => PeerState {
+                    url,
+                    state: None,
+                    error: Some(error),
+                    in_sync: true,
+                },
+            })
+            .collect();
+        let consistent = peers.iter().all(|peer| peer.in_sync);
+        let (diverged_since_unix, diverged) = self.record(consistent, unix_now());
+        ClusterReport {
+            node,
+            peers,
+            consistent,
+            diverged_since_unix,
+            diverged,
+        }
+    }
+
+    /// Note whether the nodes agree as of `now`, returning when they started
+    /// disagreeing and whether that has exceeded the threshold.
+    fn record(&self, consistent: bool, now: u64) -> (Option<u64>, bool) {
+        let mut since = self.diverged_since.lock().unwrap();
+        if consistent {
+            *since = None;
+        } else if since.is_none() {
+            *since = Some(now);
+        }
+        let elapsed = since.map_or(0, |since| now.saturating_sub(since));
+        let diverged = since.is_some() && elapsed >= self.threshold.as_secs();
+        let metrics = &self.reloader.metrics;
+        metrics.cluster_divergence_seconds.set(elapsed as i64);
+        metrics.cluster_diverged.set(diverged as i64);
+        if diverged {
+            tracing::error!(
+                divergence_secs = elapsed,
+                "nodes have served different link tables for longer than the threshold"
+            );
+        }
+        (*since, diverged)
+    }
+}
+
+/// Fetch a peer's `/cluster/node` report.
+pub async fn fetch_node_state(url: &str) -> Result<NodeState, String> {
+    let uri: hyper::Uri = url.parse().map_err(|e| format!("invalid URL: {}", e))?;
+    let response = tokio::time::timeout(PEER_TIMEOUT, https_client().get(uri))
+        .await
+        .map_err(|_| format!("timed out after {}s", PEER_TIMEOUT.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_slice(&body).map_err(|e| format!("invalid response: {}", e))
+}
+
+/// Compare with the peers every `interval` in the background, so the
+/// divergence gauges stay current without anyone calling `/cluster/status`.
+/// No-op without peers or with a zero interval.
+pub fn spawn_check_task(monitor: ClusterMonitor, interval: Duration) {
+    if monitor.peers.is_empty() || interval.is_zero() {
+        return;
+    }
+    tracing::info!(
+        peers = monitor.peers.len(),
+        interval_secs = interval.as_secs(),
+        "cluster consistency checks enabled"
+    );
+    task::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            let report = monitor.check().await;
+            if !report.consistent {
+                tracing::warn!(
+                    since = report.diverged_since_unix,
+                    "a peer is serving a different link table"
+                );
+            }
+        }
+    });
+}
+
+/// Signed requests seen within the replay window, keyed by timestamp and
+/// nonce, so a captured relay or snapshot can't be replayed while its
+/// timestamp is still fresh.
+pub struct ReplayGuard {
+    seen: TokioMutex<HashSet<(u64, String)>>,
+    pub window_secs: u64,
+}
+
+impl ReplayGuard {
+    pub fn new(window_secs: u64) -> Self {
+        ReplayGuard {
+            seen: TokioMutex::new(HashSet::new()),
+            window_secs,
+        }
+    }
+
+    /// Returns true the first time `stamp` is seen, false for a replay.
+    pub async fn first_use(&self, stamp: RelayStamp, now: u64) -> bool {
+        let mut seen = self.seen.lock().await;
+        // Stamps outside the window fail verification anyway; forget them.
+        seen.retain(|(timestamp, _)| now.abs_diff(*timestamp) <= self.window_secs);
+        seen.insert((stamp.timestamp, stamp.nonce))
+    }
+}
+
+/// Accepts tables pushed by peers (`POST /cluster/snapshot`) and serves
+/// them, so a node that can't reach git still stays current.
+#[derive(Clone)]
+pub struct SnapshotReceiver {
+    coordinator: ReloadCoordinator,
+    secret: String,
+    replay_guard: Arc<ReplayGuard>,
+    /// Signature time of the newest snapshot served; older ones (e.g. a
+    /// retry that lost a race with a newer push) are refused.
+    newest: Arc<Mutex<u64>>,
+}
+
+impl SnapshotReceiver {
+    /// Snapshots must be signed with the cluster `secret`.
+    pub fn new(
+        coordinator: ReloadCoordinator,
+        secret: String,
+        replay_guard: Arc<ReplayGuard>,
+    ) -> Self {
+        SnapshotReceiver {
+            coordinator,
+            secret,
+            replay_guard,
+            newest: Arc::new(Mutex::new(0)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SnapshotErrorBody {
+    error: String,
+}
+
+fn snapshot_error(status: StatusCode, error: impl Into<String>) -> Response {
+    let body = SnapshotErrorBody {
+        error: error.into(),
+    };
+    (status, Json(body)).into_response()
+}
+
+/// The `/cluster/node` and `/cluster/status` routes, plus
+/// `/cluster/snapshot` when this node accepts snapshots.
+pub fn routes(monitor: ClusterMonitor, receiver: Option<SnapshotReceiver>) -> Router<()> {
+    let mut router = Router::new()
+        .route(NODE_ROUTE, get(node_handler))
+        .route(STATUS_ROUTE, get(status_handler))
+        .layer(Extension(monitor));
+    if let Some(receiver) = receiver {
+        router = router.merge(
+            Router::new()
+                .route(SNAPSHOT_ROUTE, post(snapshot_handler))
+                .layer(Extension(receiver)),
+        );
+    }
+    router
+}
+
+async fn node_handler(Extension(monitor): Extension<ClusterMonitor>) -> Json<NodeState> {
+    Json(NodeState::current(
+        &monitor.reloader,
+        monitor.node_id.as_deref(),
+    ))
+}
+
+async fn status_handler(Extension(monitor): Extension<ClusterMonitor>) -> Json<ClusterReport> {
+    Json(monitor.check().await)
+}
+
+/// Serve a peer's pushed table: 200 with the reload outcome; 401 unless the
+/// body carries a fresh, unused relay signature from the cluster secret; 400
+/// for a malformed body; 422 if the table fails validation or doesn't match
+/// its hash; 409 if a newer snapshot was already served.
+async fn snapshot_handler(
+    Extension(receiver): Extension<SnapshotReceiver>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let now = unix_now();
+    let guard = &receiver.replay_guard;
+    let stamp = signature::verify_relay(&receiver.secret, &headers, &body, now, guard.window_secs);
+    let Some(stamp) = stamp else {
+        return reject_snapshot(&receiver);
+    };
+    let signed_at = stamp.timestamp;
+    if !guard.first_use(stamp, now).await {
+        return reject_snapshot(&receiver);
+    }
+    let snapshot: TableSnapshot = match serde_json::from_slice(&body) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            return snapshot_error(StatusCode::BAD_REQUEST, format!("invalid snapshot: {}", e));
+        }
+    };
+    if table_hash(&snapshot.links) != snapshot.hash {
+        return snapshot_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "snapshot links don't match its hash",
+        );
+    }
+    let _lock = receiver.coordinator.lock().await;
+    let mut newest = receiver.newest.lock().unwrap();
+    if signed_at < *newest {
+        return snapshot_error(StatusCode::CONFLICT, "a newer snapshot is already served");
+    }
+    let installed = receiver.coordinator.reloader().install(LinkSnapshot {
+        links: snapshot.links,
+        revision: snapshot.head,
+    });
+    match installed {
+        Ok(outcome) => {
+            *newest = signed_at;
+            tracing::info!(head = ?outcome.head, links = outcome.link_count, "served peer snapshot");
+            (StatusCode::OK, Json(outcome)).into_response()
+        }
+        Err(e) => snapshot_error(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+    }
+}
+
+fn reject_snapshot(receiver: &SnapshotReceiver) -> Response {
+    tracing::warn!("rejected snapshot with missing, invalid or replayed signature");
+    receiver
+        .coordinator
+        .reloader()
+        .metrics
+        .webhook_unauthorized
+        .inc();
+    StatusCode::UNAUTHORIZED.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::RouterCache;
+    use crate::config::{GitConfig, LinkFormat};
+    use crate::metrics::init_metrics;
+    use crate::reload::ReloadStatus;
+    use crate::source::GitSource;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    fn links(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(code, url)| (code.to_string(), url.to_string()))
+            .collect()
+    }
+
+    fn reloader(table: HashMap<String, String>, head: Option<&str>) -> Reloader {
+        let metrics = init_metrics();
+        let cache = RouterCache::new(table);
+        let status = ReloadStatus::new(metrics.clone(), 0, head.map(str::to_string));
+        let source = GitSource::new(GitConfig::default(), LinkFormat::Yaml);
+        Reloader::new(cache, metrics, status, Arc::new(source))
+    }
+
+    /// Serve `/cluster/node` for `reloader` on a local port, returning the
+    /// webhook URL a peer would be configured with.
+    fn spawn_peer(reloader: Reloader) -> String {
+        let app = routes(
+            ClusterMonitor::new(reloader, None, &[], Duration::ZERO),
+            None,
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        format!("http://{}/git-webhook", addr)
+    }
+
+    const SECRET: &str = "cluster secret";
+
+    /// The cluster routes of a node accepting snapshots into `reloader`.
+    fn receiving_node(reloader: Reloader) -> Router<()> {
+        let monitor = ClusterMonitor::new(reloader.clone(), None, &[], Duration::ZERO);
+        let coordinator = ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(())));
+        let receiver = SnapshotReceiver::new(
+            coordinator,
+            SECRET.to_string(),
+            Arc::new(ReplayGuard::new(300)),
+        );
+        routes(monitor, Some(receiver))
+    }
+
+    fn snapshot_request(body: &[u8], signed_at: u64, nonce: &str) -> Request<Body> {
+        Request::post(SNAPSHOT_ROUTE)
+            .header(
+                signature::RELAY_SIGNATURE_HEADER,
+                signature::sign_relay(SECRET, signed_at, nonce, body),
+            )
+            .body(Body::from(body.to_vec()))
+            .unwrap()
+    }
+
+    fn snapshot_body(table: &HashMap<String, String>, head: &str) -> Vec<u8> {
+        serde_json::to_vec(&TableSnapshot {
+            links: table.clone(),
+            head: Some(head.to_string()),
+            hash: table_hash(table),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_table_hash_ignores_order_and_detects_changes() {
+        let a = links(&[("a", "https://a"), ("b", "https://b")]);
+        let b = links(&[("b", "https://b"), ("a", "https://a")]);
+        assert_eq!(table_hash(&a), table_hash(&b));
+        let changed = links(&[("a", "https://a"), ("b", "https://c")]);
+        assert_ne!(table_hash(&a), table_hash(&changed));
+        // Entries are delimited, so moving text between code and URL counts.
+        let shifted = links(&[("ab", "ttps://a")]);
+        assert_ne!(
+            table_hash(&links(&[("a", "bttps://a")])),
+            table_hash(&shifted)
+        );
+    }
+
+    #[test]
+    fn test_peer_route_keeps_scheme_and_host() {
+        assert_eq!(
+            peer_route("https://tatooine.example:8443/git-webhook?x=1", NODE_ROUTE).as_deref(),
+            Some("https://tatooine.example:8443/cluster/node")
+        );
+        assert_eq!(peer_route("/git-webhook", NODE_ROUTE), None);
+    }
+
+    #[tokio::test]
+    async fn test_node_route_reports_hash_and_head() {
+        let table = links(&[("a", "https://a")]);
+        let app = routes(
+            ClusterMonitor::new(
+                reloader(table.clone(), Some("abc123")),
+                Some("01".to_string()),
+                &[],
+                Duration::ZERO,
+            ),
+            None,
+        );
+        let response = app
+            .oneshot(Request::get(NODE_ROUTE).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let state: NodeState = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            state,
+            NodeState {
+                hash: table_hash(&table),
+                head: Some("abc123".to_string()),
+                link_count: 1,
+                node_id: Some("01".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_reports_divergent_peer() {
+        let ours = links(&[("a", "https://a")]);
+        let same = spawn_peer(reloader(ours.clone(), None));
+        let other = spawn_peer(reloader(links(&[("a", "https://b")]), None));
+        let local = reloader(ours, None);
+        let metrics = local.metrics.clone();
+        let monitor = ClusterMonitor::new(local, None, &[same, other.clone()], Duration::ZERO);
+        let report = monitor.check().await;
+        assert!(!report.consistent);
+        assert!(report.peers[0].in_sync);
+        assert!(!report.peers[1].in_sync);
+        assert!(report.diverged_since_unix.is_some());
+        // A zero threshold alerts as soon as nodes disagree.
+        assert!(report.diverged);
+        assert_eq!(metrics.cluster_diverged.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_peer_reported_but_not_divergent() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let dead = format!("http://{}/git-webhook", listener.local_addr().unwrap());
+        drop(listener);
+        let monitor = ClusterMonitor::new(
+            reloader(HashMap::new(), None),
+            None,
+            &[dead],
+            Duration::ZERO,
+        );
+        let report = monitor.check().await;
+        assert!(report.consistent);
+        assert!(report.peers[0].error.is_some());
+        assert!(!report.diverged);
+    }
+
+    #[test]
+    fn test_divergence_alerts_only_past_threshold() {
+        let local = reloader(HashMap::new(), None);
+        let metrics = local.metrics.clone();
+        let monitor = ClusterMonitor::new(local, None, &[], Duration::from_secs(300));
+        assert_eq!(monitor.record(false, 1_000), (Some(1_000), false));
+        assert_eq!(monitor.record(false, 1_299), (Some(1_000), false));
+        assert_eq!(metrics.cluster_divergence_seconds.get(), 299);
+        assert_eq!(monitor.record(false, 1_300), (Some(1_000), true));
+        assert_eq!(metrics.cluster_diverged.get(), 1);
+        // Agreeing again clears the alert and restarts the clock.
+        assert_eq!(monitor.record(true, 1_400), (None, false));
+        assert_eq!(metrics.cluster_diverged.get(), 0);
+        assert_eq!(monitor.record(false, 1_500), (Some(1_500), false));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_route_installs_signed_table_once() {
+        let local = reloader(links(&[("a", "https://a.example")]), None);
+        let app = receiving_node(local.clone());
+        let table = links(&[("b", "https://b.example")]);
+        let body = snapshot_body(&table, "def456");
+        let now = unix_now();
+
+        let response = app
+            .clone()
+            .oneshot(snapshot_request(&body, now, "n1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(*local.cache.snapshot(), table);
+        assert_eq!(local.status.report().head.as_deref(), Some("def456"));
+
+        // Replaying the same request, or sending it unsigned, is refused.
+        let replayed = app
+            .clone()
+            .oneshot(snapshot_request(&body, now, "n1"))
+            .await
+            .unwrap();
+        assert_eq!(replayed.status(), StatusCode::UNAUTHORIZED);
+        let unsigned = Request::post(SNAPSHOT_ROUTE)
+            .body(Body::from(body))
+            .unwrap();
+        let unsigned = app.oneshot(unsigned).await.unwrap();
+        assert_eq!(unsigned.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(local.metrics.webhook_unauthorized.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_route_rejects_bad_hash_and_stale_tables() {
+        let original = links(&[("a", "https://a.example")]);
+        let local = reloader(original.clone(), None);
+        let app = receiving_node(local.clone());
+        let now = unix_now();
+
+        let mut tampered: serde_json::Value =
+            serde_json::from_slice(&snapshot_body(&original, "abc")).unwrap();
+        tampered["links"]["a"] = "https://evil.example".into();
+        let tampered = serde_json::to_vec(&tampered).unwrap();
+        let response = app
+            .clone()
+            .oneshot(snapshot_request(&tampered, now, "n1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(*local.cache.snapshot(), original);
+
+        // A snapshot signed before the one already served lost a race.
+        let newer = links(&[("a", "https://new.example")]);
+        let older = links(&[("a", "https://old.example")]);
+        let response = app
+            .clone()
+            .oneshot(snapshot_request(&snapshot_body(&newer, "new"), now, "n2"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .oneshot(snapshot_request(
+                &snapshot_body(&older, "old"),
+                now - 5,
+                "n3",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(*local.cache.snapshot(), newer);
+    }
+}
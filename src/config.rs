@@ -1,5 +1,6 @@
 // (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
-//! config module: loads links from YAML and service settings.
+//! config module: service settings, plus parsing and validation of links
+//! documents.
 
 use crate::errors::Error;
 use serde::Deserialize;
@@ -19,28 +20,511 @@ pub struct Links {
 /// Service configuration parameters.
 #[derive(Clone, Deserialize)]
 pub struct ServiceConfig {
-    /// The address (host:port) to bind the HTTP server to.
+    /// The address (host:port) to bind the HTTP server to. Ignored when
+    /// `listeners` is set.
     #[serde(default = "default_address")]
     pub address: String,
+    /// Sockets to serve on in place of `address`: TCP, Unix domain or
+    /// inherited from systemd, each optionally limited to some route groups.
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+    /// Address (host:port) for a separate admin listener serving metrics,
+    /// the reload webhook and status, the write API and the cluster
+    /// endpoints. When set, the other listeners serve only the public
+    /// routes unless their `routes` say otherwise, so the forge's webhook
+    /// and `peer_urls` (relays, and the cluster monitor's and election's
+    /// `/cluster/node` queries) must target this port.
+    #[serde(default)]
+    pub admin_address: Option<String>,
+    /// Also accept cleartext HTTP/2 (h2c, prior knowledge) on `address`,
+    /// for proxies that speak h2 upstream. `listeners` set their own.
+    #[serde(default)]
+    pub h2c: bool,
     /// (Deprecated) Reload interval no longer used; webhook triggers reload.
     // pub reload_interval_secs: u64,
     /// HTTP path for the reload webhook endpoint.
     #[serde(default = "default_webhook_path")]
     pub webhook_path: String,
+    /// Longest, in seconds, a synchronous (`?wait=true`) webhook request is
+    /// held waiting for its reload before answering 504.
+    #[serde(default = "default_webhook_sync_timeout_secs")]
+    pub webhook_sync_timeout_secs: u64,
+    /// Shared secret webhook requests must be signed with (GitHub/Gitea
+    /// `X-Hub-Signature-256`, or a GitLab `X-Gitlab-Token`). Absent = any
+    /// request passing the rate limiter may trigger a reload.
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Branch whose pushes trigger a reload. `None` derives it from the
+    /// configured git ref; an empty string accepts pushes to any ref.
+    #[serde(default)]
+    pub webhook_branch: Option<String>,
+    /// Paths a push must touch to trigger a reload (entries ending in `/`
+    /// match a directory). Empty = every push to the branch reloads.
+    #[serde(default)]
+    pub webhook_paths: Vec<String>,
     /// Max reload webhook requests per minute per IP.
     #[serde(default = "default_rate_limit_minute")]
     pub rate_limit_per_minute: u32,
     /// Max reload webhook requests per day per IP.
     #[serde(default = "default_rate_limit_day")]
     pub rate_limit_per_day: u32,
-    /// Optional peer webhook URL to relay reload webhooks to (e.g. the
-    /// standby node's `/git-webhook` endpoint). Absent = relay disabled.
+    /// Peer webhook URLs to relay reload webhooks to (e.g. the other nodes'
+    /// `/git-webhook` endpoints), relayed concurrently. Empty = relay
+    /// disabled.
     #[serde(default)]
-    pub peer_url: Option<String>,
-    /// Interval, in seconds, between background `git pull` + reload polls.
-    /// `None` (or `0`) disables polling.
+    pub peer_urls: Vec<String>,
+    /// How long, in seconds, a failed relay to a peer keeps being retried
+    /// (with backoff) before it is given up on.
+    #[serde(default = "default_relay_retry_secs")]
+    pub relay_retry_secs: u64,
+    /// File pending relays are kept in, so they survive a restart. `None`
+    /// keeps them in memory only.
+    #[serde(default)]
+    pub relay_outbox_path: Option<String>,
+    /// Interval, in seconds, between background reload polls: a git fetch
+    /// and read of the links file in git mode, a fetch in http mode, and a
+    /// `data_version` check with sqlite `trigger = "poll"`. `None` (or `0`)
+    /// disables polling.
     #[serde(default = "default_poll_interval_secs")]
     pub poll_interval_secs: Option<u64>,
+    /// Longest, in seconds, the poll task backs off to while reloads keep
+    /// failing (never less than the poll interval).
+    #[serde(default = "default_poll_max_backoff_secs")]
+    pub poll_max_backoff_secs: u64,
+    /// Consecutive failed polls after which each further failure is logged
+    /// at error rather than warn level.
+    #[serde(default = "default_poll_failure_alert_threshold")]
+    pub poll_failure_alert_threshold: u32,
+    /// Where reloads fetch and read `links.yaml` from.
+    #[serde(default)]
+    pub git: GitConfig,
+    /// What triggers reloads and where they read links from.
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    /// SQLite link store, used in `sqlite` reload mode.
+    #[serde(default)]
+    pub sqlite: SqliteConfig,
+    /// Remote links file, used in `http` reload mode.
+    #[serde(default)]
+    pub remote: RemoteConfig,
+    /// Authenticated write API.
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// Settings shared by the nodes of a multi-node deployment.
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    /// Signal-driven graceful shutdown settings.
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    /// Built-in HTTPS listener.
+    #[serde(default)]
+    pub tls: TlsConfig,
+}
+
+/// One socket the HTTP server serves on (a `[[listeners]]` entry).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct ListenerConfig {
+    #[serde(default)]
+    pub kind: ListenerKind,
+    /// `tcp`: host:port to bind.
+    #[serde(default)]
+    pub address: Option<String>,
+    /// `unix`: path of the socket file. A stale socket left there by a
+    /// previous run is replaced.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// `unix`: permission bits for the socket file, e.g. `0o660`.
+    #[serde(default)]
+    pub mode: Option<u32>,
+    /// `unix`: user (name or uid) to own the socket file.
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// `unix`: group (name or gid) to own the socket file.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// `systemd`: the inherited socket's `FileDescriptorName=` (by default
+    /// the socket unit's name). `None` takes the first one passed.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Route groups served here. Empty = all of them.
+    #[serde(default)]
+    pub routes: Vec<RouteGroup>,
+    /// Also accept cleartext HTTP/2 (h2c, prior knowledge).
+    #[serde(default)]
+    pub h2c: bool,
+}
+
+/// Where a listener's socket comes from.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerKind {
+    /// Bind a TCP `address`.
+    #[default]
+    Tcp,
+    /// Bind a Unix domain socket at `path`.
+    Unix,
+    /// Take a socket passed in by systemd socket activation (`LISTEN_FDS`).
+    Systemd,
+}
+
+/// The sets of routes a listener can be limited to.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteGroup {
+    /// Redirects, static files, `/healthz`, `/version` and `/available`.
+    Public,
+    /// `/metrics`.
+    Metrics,
+    /// The reload webhook and `/reload/status`.
+    Reload,
+    /// The write API (`/api/links`).
+    Api,
+    /// Node-to-node `/cluster` endpoints.
+    Cluster,
+}
+
+impl RouteGroup {
+    pub const ALL: &'static [RouteGroup] = &[
+        RouteGroup::Public,
+        RouteGroup::Metrics,
+        RouteGroup::Reload,
+        RouteGroup::Api,
+        RouteGroup::Cluster,
+    ];
+
+    /// Everything but the public routes, served by the admin listener.
+    pub const ADMIN: &'static [RouteGroup] = &[
+        RouteGroup::Metrics,
+        RouteGroup::Reload,
+        RouteGroup::Api,
+        RouteGroup::Cluster,
+    ];
+}
+
+/// Built-in TLS termination. Off unless `address`, `cert_path` and
+/// `key_path` are all set.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TlsConfig {
+    /// Address for the HTTPS listener, e.g. "0.0.0.0:8443".
+    #[serde(default)]
+    pub address: Option<String>,
+    /// PEM certificate chain, leaf first.
+    #[serde(default)]
+    pub cert_path: Option<String>,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1).
+    #[serde(default)]
+    pub key_path: Option<String>,
+    /// Refuse TLS 1.2 handshakes.
+    #[serde(default)]
+    pub tls13_only: bool,
+    /// Answer requests for the public routes on the plain HTTP listeners
+    /// with a 308 redirect to the HTTPS listener instead of serving them.
+    #[serde(default)]
+    pub redirect_http: bool,
+    /// Interval, in seconds, between checks of the certificate and key files
+    /// for changes; a changed pair is loaded without a restart. `0` disables.
+    #[serde(default = "default_tls_reload_check_secs")]
+    pub reload_check_secs: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            address: None,
+            cert_path: None,
+            key_path: None,
+            tls13_only: false,
+            redirect_http: false,
+            reload_check_secs: default_tls_reload_check_secs(),
+        }
+    }
+}
+
+/// Graceful shutdown settings, applied on SIGTERM/SIGINT.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ShutdownConfig {
+    /// Seconds to keep serving, with `/healthz` already reporting "draining",
+    /// before the listener stops accepting connections. Gives load balancers
+    /// time to take the node out of rotation.
+    #[serde(default)]
+    pub pre_drain_secs: u64,
+    /// Seconds to wait for in-flight requests to finish once the listener
+    /// has stopped accepting, before exiting anyway.
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            pre_drain_secs: 0,
+            drain_timeout_secs: default_drain_timeout_secs(),
+        }
+    }
+}
+
+/// How the link table is kept fresh.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReloadMode {
+    /// Fetch the links repo and read the links file from a git ref
+    /// (webhook- and poll-triggered).
+    #[default]
+    Git,
+    /// Watch the links file on disk and reload when it changes; git is not
+    /// used at all (e.g. links.yaml mounted from a ConfigMap).
+    Watch,
+    /// Read the link table from a SQLite database; see `SqliteConfig`.
+    Sqlite,
+    /// Fetch the links file over HTTP(S); see `RemoteConfig`. Reloads are
+    /// poll- and webhook-triggered, like git mode.
+    Http,
+}
+
+/// Write API settings (`POST`/`PUT`/`DELETE /api/links/{code}`, git mode
+/// only). Changes are committed to the links repo and pushed.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ApiConfig {
+    /// Bearer token API requests must carry. `None` disables the API.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Author (and committer) name of the commits the API makes.
+    #[serde(default = "default_api_author_name")]
+    pub author_name: String,
+    /// Author (and committer) email of the commits the API makes.
+    #[serde(default = "default_api_author_email")]
+    pub author_email: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            token: None,
+            author_name: default_api_author_name(),
+            author_email: default_api_author_email(),
+        }
+    }
+}
+
+/// How a node brings its peers up to date after a reload.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerSync {
+    /// Relay the reload webhook; each peer then reloads from its own source.
+    #[default]
+    Relay,
+    /// Push the reloaded table itself, signed with the cluster secret, so
+    /// peers serve it without needing access to the source (git).
+    Snapshot,
+}
+
+/// Settings shared by the nodes of a multi-node deployment.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ClusterConfig {
+    /// Secret every node shares. When set, webhook relays are signed with it
+    /// (timestamped HMAC), and a request claiming to be a relay is only
+    /// trusted if its signature verifies. `None` trusts the bare relay
+    /// header, as before.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// How far, in seconds, a signed relay's timestamp may be from this
+    /// node's clock; a relay is also rejected if seen before within it.
+    #[serde(default = "default_cluster_replay_window_secs")]
+    pub replay_window_secs: u64,
+    /// Interval, in seconds, between background comparisons of this node's
+    /// link table with its peers'. `0` disables them (`/cluster/status`
+    /// still compares on request).
+    #[serde(default = "default_cluster_check_interval_secs")]
+    pub check_interval_secs: u64,
+    /// How long, in seconds, nodes may serve different tables (e.g. while a
+    /// relay is in flight) before `cluster_diverged` is raised.
+    #[serde(default = "default_cluster_divergence_threshold_secs")]
+    pub divergence_threshold_secs: u64,
+    /// How `peer_urls` are kept up to date. `Snapshot` needs `secret`.
+    #[serde(default)]
+    pub sync: PeerSync,
+    /// This node's ID for leader election; IDs must be unique across the
+    /// cluster and are compared as strings.
+    #[serde(default)]
+    pub node_id: Option<String>,
+    /// Elect a leader among this node and `peer_urls` (the lowest node ID
+    /// heard from recently); only the leader polls, and it relays what its
+    /// polls pick up. Needs `node_id`.
+    #[serde(default)]
+    pub leader_election: bool,
+    /// Interval, in seconds, between heartbeats to peers.
+    #[serde(default = "default_cluster_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// How long, in seconds, a peer may go unheard before it no longer
+    /// counts for the election, so a lost leader is replaced.
+    #[serde(default = "default_cluster_leader_timeout_secs")]
+    pub leader_timeout_secs: u64,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        ClusterConfig {
+            secret: None,
+            replay_window_secs: default_cluster_replay_window_secs(),
+            check_interval_secs: default_cluster_check_interval_secs(),
+            divergence_threshold_secs: default_cluster_divergence_threshold_secs(),
+            sync: PeerSync::default(),
+            node_id: None,
+            leader_election: false,
+            heartbeat_interval_secs: default_cluster_heartbeat_interval_secs(),
+            leader_timeout_secs: default_cluster_leader_timeout_secs(),
+        }
+    }
+}
+
+/// Remote links file settings, used when `reload.mode = "http"`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct RemoteConfig {
+    /// URL of the links file. Its extension picks the format unless
+    /// `reload.format` is set.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Seconds a fetch (request and body) may take before it fails.
+    #[serde(default = "default_remote_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        RemoteConfig {
+            url: None,
+            timeout_secs: default_remote_timeout_secs(),
+        }
+    }
+}
+
+/// What reloads a SQLite-backed link table.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SqliteTrigger {
+    /// Poll the database file (and its `-wal`) modification times.
+    #[default]
+    Mtime,
+    /// Reload every `poll.interval_secs`, changed or not.
+    Poll,
+    /// Only reload when the webhook (or SIGHUP) says so.
+    Webhook,
+}
+
+/// SQLite link store settings, used when `reload.mode = "sqlite"`. The whole
+/// `code`/`url` table is read on every reload and validated like any links
+/// file.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SqliteConfig {
+    /// Database file, opened read-only.
+    #[serde(default = "default_sqlite_path")]
+    pub path: String,
+    /// Table holding the links, with `code` and `url` text columns.
+    #[serde(default = "default_sqlite_table")]
+    pub table: String,
+    /// What triggers a reload; see `SqliteTrigger`.
+    #[serde(default)]
+    pub trigger: SqliteTrigger,
+    /// How often, in milliseconds, the `mtime` trigger checks the files.
+    #[serde(default = "default_sqlite_check_interval_ms")]
+    pub check_interval_ms: u64,
+}
+
+impl Default for SqliteConfig {
+    fn default() -> Self {
+        SqliteConfig {
+            path: default_sqlite_path(),
+            table: default_sqlite_table(),
+            trigger: SqliteTrigger::default(),
+            check_interval_ms: default_sqlite_check_interval_ms(),
+        }
+    }
+}
+
+/// Document format of the links file; see `source::LinkFormat::parse`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkFormat {
+    /// `code: url` mapping (the classic links.yaml).
+    Yaml,
+    /// A flat `{"code": "url"}` object.
+    Json,
+    /// A flat table of `code = "url"` pairs.
+    Toml,
+    /// `code,url` rows, optionally with a header row and extra columns.
+    Csv,
+}
+
+/// Reload trigger settings.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ReloadConfig {
+    /// Reload mode; see `ReloadMode`.
+    #[serde(default)]
+    pub mode: ReloadMode,
+    /// Format of the links file. `None` infers it from the file extension
+    /// (falling back to YAML).
+    #[serde(default)]
+    pub format: Option<LinkFormat>,
+    /// Links file read (and, in watch mode, watched) on disk.
+    #[serde(default = "default_watch_path")]
+    pub watch_path: String,
+    /// Directory of further links files merged into the table in watch mode
+    /// (searched recursively; each file's format comes from its extension).
+    /// Watched recursively alongside `watch_path`.
+    #[serde(default)]
+    pub include_dir: Option<String>,
+    /// Quiet period, in milliseconds, a burst of file events must settle for
+    /// before a watch-mode reload runs.
+    #[serde(default = "default_watch_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        ReloadConfig {
+            mode: ReloadMode::default(),
+            format: None,
+            watch_path: default_watch_path(),
+            include_dir: None,
+            debounce_ms: default_watch_debounce_ms(),
+        }
+    }
+}
+
+/// Git settings for reloads. Reloads `git fetch` the remote and read the
+/// links file straight out of `git_ref`'s tree, so the repository can be bare
+/// and there is no working tree to dirty or half-write.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct GitConfig {
+    /// Directory of the links repository (bare or regular clone).
+    #[serde(default = "default_git_repo_dir")]
+    pub repo_dir: String,
+    /// Remote to fetch before each reload.
+    #[serde(default = "default_git_remote")]
+    pub remote: String,
+    /// Ref whose tree the links file is read from.
+    #[serde(rename = "ref", default = "default_git_ref")]
+    pub git_ref: String,
+    /// Path of the links file within that tree.
+    #[serde(default = "default_git_links_path")]
+    pub links_path: String,
+    /// Fingerprints of the SSH (`SHA256:...`) or GPG keys trusted to sign
+    /// the links repo. When non-empty, a reload is refused unless the ref's
+    /// commit is signed by one of them. Empty = no signing policy.
+    #[serde(default)]
+    pub trusted_signing_keys: Vec<String>,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        GitConfig {
+            repo_dir: default_git_repo_dir(),
+            remote: default_git_remote(),
+            git_ref: default_git_ref(),
+            links_path: default_git_links_path(),
+            trusted_signing_keys: Vec::new(),
+        }
+    }
 }
 
 fn default_address() -> String {
@@ -51,6 +535,10 @@ fn default_webhook_path() -> String {
     "/git-webhook".to_string()
 }
 
+fn default_webhook_sync_timeout_secs() -> u64 {
+    60
+}
+
 fn default_rate_limit_minute() -> u32 {
     30
 }
@@ -63,61 +551,255 @@ fn default_poll_interval_secs() -> Option<u64> {
     Some(60)
 }
 
-/// Overall application configuration.
-pub struct Config {
-    /// Mapping of codes to target URLs.
-    pub links: HashMap<String, String>,
-    /// Service settings.
-    pub service: ServiceConfig,
+fn default_poll_max_backoff_secs() -> u64 {
+    900
+}
+
+fn default_poll_failure_alert_threshold() -> u32 {
+    5
+}
+
+fn default_watch_path() -> String {
+    "links.yaml".to_string()
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    500
+}
+
+fn default_sqlite_path() -> String {
+    "links.db".to_string()
+}
+
+fn default_sqlite_table() -> String {
+    "links".to_string()
+}
+
+fn default_sqlite_check_interval_ms() -> u64 {
+    1000
+}
+
+fn default_api_author_name() -> String {
+    "redirective".to_string()
+}
+
+fn default_api_author_email() -> String {
+    "redirective@localhost".to_string()
+}
+
+fn default_relay_retry_secs() -> u64 {
+    300
+}
+
+fn default_cluster_replay_window_secs() -> u64 {
+    300
+}
+
+fn default_cluster_check_interval_secs() -> u64 {
+    60
+}
+
+fn default_cluster_divergence_threshold_secs() -> u64 {
+    300
+}
+
+fn default_tls_reload_check_secs() -> u64 {
+    60
+}
+
+fn default_cluster_heartbeat_interval_secs() -> u64 {
+    10
+}
+
+fn default_cluster_leader_timeout_secs() -> u64 {
+    30
+}
+
+fn default_remote_timeout_secs() -> u64 {
+    10
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+fn default_git_repo_dir() -> String {
+    ".".to_string()
+}
+
+fn default_git_remote() -> String {
+    "origin".to_string()
+}
+
+fn default_git_ref() -> String {
+    "origin/main".to_string()
+}
+
+fn default_git_links_path() -> String {
+    "links.yaml".to_string()
 }
 
 #[derive(Deserialize)]
 struct RawWebhookConfig {
     path: Option<String>,
+    sync_timeout_secs: Option<u64>,
+    secret: Option<String>,
+    branch: Option<String>,
+    paths: Option<Vec<String>>,
     rate_limit_per_minute: Option<u32>,
     rate_limit_per_day: Option<u32>,
+    /// Single-peer shorthand, appended to `peer_urls`.
     peer_url: Option<String>,
+    peer_urls: Option<Vec<String>>,
+    relay_retry_secs: Option<u64>,
+    relay_outbox_path: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct RawPollConfig {
     interval_secs: Option<u64>,
+    max_backoff_secs: Option<u64>,
+    failure_alert_threshold: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RawGitConfig {
+    repo_dir: Option<String>,
+    remote: Option<String>,
+    #[serde(rename = "ref")]
+    git_ref: Option<String>,
+    links_path: Option<String>,
+    trusted_signing_keys: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct RawReloadConfig {
+    mode: Option<ReloadMode>,
+    format: Option<LinkFormat>,
+    watch_path: Option<String>,
+    include_dir: Option<String>,
+    debounce_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawSqliteConfig {
+    path: Option<String>,
+    table: Option<String>,
+    trigger: Option<SqliteTrigger>,
+    check_interval_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawRemoteConfig {
+    url: Option<String>,
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawApiConfig {
+    token: Option<String>,
+    author_name: Option<String>,
+    author_email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawClusterConfig {
+    secret: Option<String>,
+    replay_window_secs: Option<u64>,
+    check_interval_secs: Option<u64>,
+    divergence_threshold_secs: Option<u64>,
+    sync: Option<PeerSync>,
+    node_id: Option<String>,
+    leader_election: Option<bool>,
+    heartbeat_interval_secs: Option<u64>,
+    leader_timeout_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawTlsConfig {
+    address: Option<String>,
+    cert_path: Option<String>,
+    key_path: Option<String>,
+    tls13_only: Option<bool>,
+    redirect_http: Option<bool>,
+    reload_check_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawShutdownConfig {
+    pre_drain_secs: Option<u64>,
+    drain_timeout_secs: Option<u64>,
 }
 
 #[derive(Deserialize)]
 struct RawServiceConfig {
     address: Option<String>,
+    listeners: Option<Vec<ListenerConfig>>,
+    admin_address: Option<String>,
+    h2c: Option<bool>,
     webhook: Option<RawWebhookConfig>,
     poll: Option<RawPollConfig>,
+    git: Option<RawGitConfig>,
+    reload: Option<RawReloadConfig>,
+    sqlite: Option<RawSqliteConfig>,
+    remote: Option<RawRemoteConfig>,
+    api: Option<RawApiConfig>,
+    cluster: Option<RawClusterConfig>,
+    shutdown: Option<RawShutdownConfig>,
+    tls: Option<RawTlsConfig>,
 }
 
-impl Config {
-    /// Load configuration from `links.yaml` and optional service settings.
-    pub fn load(links_path: &str) -> Result<Self, Error> {
-        // Read links file and parse mappings
-        let content = fs::read_to_string(links_path)?;
-
-        // Parse YAML using serde_yaml
-        let links_data: Links = serde_yaml::from_str(&content)
-            .map_err(|e| Error::Config(format!("Failed to parse YAML: {}", e)))?;
+/// Parse and validate a links document (the contents of `links.yaml`).
+pub fn parse_links(content: &str) -> Result<HashMap<String, String>, Error> {
+    // Parse YAML using serde_yaml
+    let links_data: Links = serde_yaml::from_str(content)
+        .map_err(|e| Error::Config(format!("Failed to parse YAML: {}", e)))?;
 
-        let links = links_data.links;
+    validate_links(links_data.links)
+}
 
-        // Validate URLs are not empty
-        for (key, url) in &links {
-            if url.trim().is_empty() {
-                return Err(Error::Config(format!("Empty URL for key '{}'", key)));
-            }
+/// Validation every link table goes through, whatever its source format.
+pub fn validate_links(links: HashMap<String, String>) -> Result<HashMap<String, String>, Error> {
+    // Validate URLs are not empty
+    for (key, url) in &links {
+        if url.trim().is_empty() {
+            return Err(Error::Config(format!("Empty URL for key '{}'", key)));
         }
+    }
+    Ok(links)
+}
 
+impl ServiceConfig {
+    /// Load service settings: built-in defaults, then `redirective.toml` (if
+    /// present), then `REDIRECTIVE_*` env overrides.
+    pub fn load() -> Result<Self, Error> {
         // Default settings
         let mut service = ServiceConfig {
             address: default_address(),
+            listeners: Vec::new(),
+            admin_address: None,
+            h2c: false,
             webhook_path: default_webhook_path(),
+            webhook_sync_timeout_secs: default_webhook_sync_timeout_secs(),
+            webhook_secret: None,
+            webhook_branch: None,
+            webhook_paths: Vec::new(),
             rate_limit_per_minute: default_rate_limit_minute(),
             rate_limit_per_day: default_rate_limit_day(),
-            peer_url: None,
+            peer_urls: Vec::new(),
+            relay_retry_secs: default_relay_retry_secs(),
+            relay_outbox_path: None,
             poll_interval_secs: default_poll_interval_secs(),
+            poll_max_backoff_secs: default_poll_max_backoff_secs(),
+            poll_failure_alert_threshold: default_poll_failure_alert_threshold(),
+            git: GitConfig::default(),
+            reload: ReloadConfig::default(),
+            sqlite: SqliteConfig::default(),
+            remote: RemoteConfig::default(),
+            api: ApiConfig::default(),
+            cluster: ClusterConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            tls: TlsConfig::default(),
         };
 
         // Read service settings from redirective.toml, if available
@@ -126,30 +808,192 @@ impl Config {
             if let Some(addr) = raw.address {
                 service.address = addr;
             }
+            if let Some(listeners) = raw.listeners {
+                service.listeners = listeners;
+            }
+            if let Some(admin_address) = raw.admin_address {
+                service.admin_address = Some(admin_address);
+            }
+            if let Some(h2c) = raw.h2c {
+                service.h2c = h2c;
+            }
             if let Some(webhook_raw) = raw.webhook {
                 if let Some(path) = webhook_raw.path {
                     service.webhook_path = path;
                 }
+                if let Some(secs) = webhook_raw.sync_timeout_secs {
+                    service.webhook_sync_timeout_secs = secs;
+                }
+                if let Some(secret) = webhook_raw.secret {
+                    service.webhook_secret = Some(secret);
+                }
+                if let Some(branch) = webhook_raw.branch {
+                    service.webhook_branch = Some(branch);
+                }
+                if let Some(paths) = webhook_raw.paths {
+                    service.webhook_paths = paths;
+                }
                 if let Some(min) = webhook_raw.rate_limit_per_minute {
                     service.rate_limit_per_minute = min;
                 }
                 if let Some(day) = webhook_raw.rate_limit_per_day {
                     service.rate_limit_per_day = day;
                 }
-                if let Some(peer) = webhook_raw.peer_url {
-                    service.peer_url = Some(peer);
+                if let Some(peers) = webhook_raw.peer_urls {
+                    service.peer_urls = peers;
+                }
+                if let Some(peer) = webhook_raw.peer_url
+                    && !service.peer_urls.contains(&peer)
+                {
+                    service.peer_urls.push(peer);
+                }
+                if let Some(secs) = webhook_raw.relay_retry_secs {
+                    service.relay_retry_secs = secs;
+                }
+                if let Some(path) = webhook_raw.relay_outbox_path {
+                    service.relay_outbox_path = Some(path);
+                }
+            }
+            if let Some(poll_raw) = raw.poll {
+                if let Some(secs) = poll_raw.interval_secs {
+                    service.poll_interval_secs = Some(secs);
+                }
+                if let Some(secs) = poll_raw.max_backoff_secs {
+                    service.poll_max_backoff_secs = secs;
+                }
+                if let Some(threshold) = poll_raw.failure_alert_threshold {
+                    service.poll_failure_alert_threshold = threshold;
+                }
+            }
+            if let Some(git_raw) = raw.git {
+                if let Some(dir) = git_raw.repo_dir {
+                    service.git.repo_dir = dir;
+                }
+                if let Some(remote) = git_raw.remote {
+                    service.git.remote = remote;
+                }
+                if let Some(git_ref) = git_raw.git_ref {
+                    service.git.git_ref = git_ref;
+                }
+                if let Some(path) = git_raw.links_path {
+                    service.git.links_path = path;
+                }
+                if let Some(keys) = git_raw.trusted_signing_keys {
+                    service.git.trusted_signing_keys = keys;
+                }
+            }
+            if let Some(reload_raw) = raw.reload {
+                if let Some(mode) = reload_raw.mode {
+                    service.reload.mode = mode;
+                }
+                if let Some(format) = reload_raw.format {
+                    service.reload.format = Some(format);
+                }
+                if let Some(path) = reload_raw.watch_path {
+                    service.reload.watch_path = path;
+                }
+                if let Some(dir) = reload_raw.include_dir {
+                    service.reload.include_dir = Some(dir);
+                }
+                if let Some(ms) = reload_raw.debounce_ms {
+                    service.reload.debounce_ms = ms;
+                }
+            }
+            if let Some(sqlite_raw) = raw.sqlite {
+                if let Some(path) = sqlite_raw.path {
+                    service.sqlite.path = path;
+                }
+                if let Some(table) = sqlite_raw.table {
+                    service.sqlite.table = table;
+                }
+                if let Some(trigger) = sqlite_raw.trigger {
+                    service.sqlite.trigger = trigger;
+                }
+                if let Some(ms) = sqlite_raw.check_interval_ms {
+                    service.sqlite.check_interval_ms = ms;
+                }
+            }
+            if let Some(remote_raw) = raw.remote {
+                if let Some(url) = remote_raw.url {
+                    service.remote.url = Some(url);
+                }
+                if let Some(secs) = remote_raw.timeout_secs {
+                    service.remote.timeout_secs = secs;
+                }
+            }
+            if let Some(api_raw) = raw.api {
+                if let Some(token) = api_raw.token {
+                    service.api.token = Some(token);
+                }
+                if let Some(name) = api_raw.author_name {
+                    service.api.author_name = name;
+                }
+                if let Some(email) = api_raw.author_email {
+                    service.api.author_email = email;
+                }
+            }
+            if let Some(cluster_raw) = raw.cluster {
+                if let Some(secret) = cluster_raw.secret {
+                    service.cluster.secret = Some(secret);
+                }
+                if let Some(secs) = cluster_raw.replay_window_secs {
+                    service.cluster.replay_window_secs = secs;
+                }
+                if let Some(secs) = cluster_raw.check_interval_secs {
+                    service.cluster.check_interval_secs = secs;
+                }
+                if let Some(secs) = cluster_raw.divergence_threshold_secs {
+                    service.cluster.divergence_threshold_secs = secs;
+                }
+                if let Some(sync) = cluster_raw.sync {
+                    service.cluster.sync = sync;
+                }
+                if let Some(node_id) = cluster_raw.node_id {
+                    service.cluster.node_id = Some(node_id);
+                }
+                if let Some(enabled) = cluster_raw.leader_election {
+                    service.cluster.leader_election = enabled;
+                }
+                if let Some(secs) = cluster_raw.heartbeat_interval_secs {
+                    service.cluster.heartbeat_interval_secs = secs;
+                }
+                if let Some(secs) = cluster_raw.leader_timeout_secs {
+                    service.cluster.leader_timeout_secs = secs;
+                }
+            }
+            if let Some(shutdown_raw) = raw.shutdown {
+                if let Some(secs) = shutdown_raw.pre_drain_secs {
+                    service.shutdown.pre_drain_secs = secs;
+                }
+                if let Some(secs) = shutdown_raw.drain_timeout_secs {
+                    service.shutdown.drain_timeout_secs = secs;
                 }
             }
-            if let Some(poll_raw) = raw.poll
-                && let Some(secs) = poll_raw.interval_secs
-            {
-                service.poll_interval_secs = Some(secs);
+            if let Some(tls_raw) = raw.tls {
+                if let Some(address) = tls_raw.address {
+                    service.tls.address = Some(address);
+                }
+                if let Some(path) = tls_raw.cert_path {
+                    service.tls.cert_path = Some(path);
+                }
+                if let Some(path) = tls_raw.key_path {
+                    service.tls.key_path = Some(path);
+                }
+                if let Some(only) = tls_raw.tls13_only {
+                    service.tls.tls13_only = only;
+                }
+                if let Some(redirect) = tls_raw.redirect_http {
+                    service.tls.redirect_http = redirect;
+                }
+                if let Some(secs) = tls_raw.reload_check_secs {
+                    service.tls.reload_check_secs = secs;
+                }
             }
         }
 
         apply_env_overrides(&mut service);
 
-        Ok(Config { links, service })
+        Ok(service)
     }
 }
 
@@ -185,14 +1029,54 @@ fn apply_env_overrides(service: &mut ServiceConfig) {
     if let Some(day) = env_override::<u32>("REDIRECTIVE_RATE_LIMIT_PER_DAY") {
         service.rate_limit_per_day = day;
     }
-    if let Ok(peer) = env::var("REDIRECTIVE_PEER_URL") {
-        let trimmed = peer.trim();
-        service.peer_url = if trimmed.is_empty() {
+    // REDIRECTIVE_PEER_URL predates the list; both take a comma-separated
+    // list, and the plural wins if both are set.
+    for key in ["REDIRECTIVE_PEER_URL", "REDIRECTIVE_PEER_URLS"] {
+        if let Ok(peers) = env::var(key) {
+            service.peer_urls = peers
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+    }
+    if let Ok(secret) = env::var("REDIRECTIVE_WEBHOOK_SECRET") {
+        service.webhook_secret = if secret.is_empty() {
+            None
+        } else {
+            Some(secret)
+        };
+    }
+    if let Ok(secret) = env::var("REDIRECTIVE_CLUSTER_SECRET") {
+        service.cluster.secret = if secret.is_empty() {
+            None
+        } else {
+            Some(secret)
+        };
+    }
+    if let Ok(node_id) = env::var("REDIRECTIVE_NODE_ID") {
+        service.cluster.node_id = if node_id.is_empty() {
             None
         } else {
-            Some(trimmed.to_string())
+            Some(node_id)
         };
     }
+    if let Ok(address) = env::var("REDIRECTIVE_ADMIN_ADDRESS") {
+        service.admin_address = if address.is_empty() {
+            None
+        } else {
+            Some(address)
+        };
+    }
+    if let Ok(token) = env::var("REDIRECTIVE_API_TOKEN") {
+        service.api.token = if token.is_empty() { None } else { Some(token) };
+    }
+    if let Some(git_ref) = env_override::<String>("REDIRECTIVE_GIT_REF")
+        && !git_ref.trim().is_empty()
+    {
+        service.git.git_ref = git_ref.trim().to_string();
+    }
 }
 
 #[cfg(test)]
@@ -227,11 +1111,30 @@ mod tests {
     fn baseline_service() -> ServiceConfig {
         ServiceConfig {
             address: default_address(),
+            listeners: Vec::new(),
+            admin_address: None,
+            h2c: false,
             webhook_path: default_webhook_path(),
+            webhook_sync_timeout_secs: default_webhook_sync_timeout_secs(),
+            webhook_secret: None,
+            webhook_branch: None,
+            webhook_paths: Vec::new(),
             rate_limit_per_minute: default_rate_limit_minute(),
             rate_limit_per_day: default_rate_limit_day(),
-            peer_url: None,
+            peer_urls: Vec::new(),
+            relay_retry_secs: default_relay_retry_secs(),
+            relay_outbox_path: None,
             poll_interval_secs: default_poll_interval_secs(),
+            poll_max_backoff_secs: default_poll_max_backoff_secs(),
+            poll_failure_alert_threshold: default_poll_failure_alert_threshold(),
+            git: GitConfig::default(),
+            reload: ReloadConfig::default(),
+            sqlite: SqliteConfig::default(),
+            remote: RemoteConfig::default(),
+            api: ApiConfig::default(),
+            cluster: ClusterConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            tls: TlsConfig::default(),
         }
     }
 
@@ -304,9 +1207,23 @@ mod tests {
         let _lock = env_lock().lock().unwrap();
         let _guard = EnvGuard::set("REDIRECTIVE_PEER_URL", "   ");
         let mut service = baseline_service();
-        service.peer_url = Some("https://old/git-webhook".to_string());
+        service.peer_urls = vec!["https://old/git-webhook".to_string()];
         apply_env_overrides(&mut service);
-        assert_eq!(service.peer_url, None);
+        assert!(service.peer_urls.is_empty());
+    }
+
+    #[test]
+    fn test_webhook_secret_env_sets_and_clears() {
+        let _lock = env_lock().lock().unwrap();
+        let mut service = baseline_service();
+        {
+            let _guard = EnvGuard::set("REDIRECTIVE_WEBHOOK_SECRET", "s3cret");
+            apply_env_overrides(&mut service);
+        }
+        assert_eq!(service.webhook_secret.as_deref(), Some("s3cret"));
+        let _guard = EnvGuard::set("REDIRECTIVE_WEBHOOK_SECRET", "");
+        apply_env_overrides(&mut service);
+        assert_eq!(service.webhook_secret, None);
     }
 
     #[test]
@@ -315,9 +1232,217 @@ mod tests {
         let _guard = EnvGuard::set("REDIRECTIVE_PEER_URL", "https://peer/git-webhook");
         let mut service = baseline_service();
         apply_env_overrides(&mut service);
+        assert_eq!(service.peer_urls, vec!["https://peer/git-webhook"]);
+    }
+
+    #[test]
+    fn test_peer_urls_env_splits_list() {
+        let _lock = env_lock().lock().unwrap();
+        let _guard = EnvGuard::set(
+            "REDIRECTIVE_PEER_URLS",
+            "https://b/git-webhook, https://c/git-webhook,",
+        );
+        let mut service = baseline_service();
+        apply_env_overrides(&mut service);
         assert_eq!(
-            service.peer_url,
-            Some("https://peer/git-webhook".to_string())
+            service.peer_urls,
+            vec!["https://b/git-webhook", "https://c/git-webhook"]
         );
     }
+
+    #[test]
+    fn test_git_ref_env_sets_value() {
+        let _lock = env_lock().lock().unwrap();
+        let _guard = EnvGuard::set("REDIRECTIVE_GIT_REF", "origin/release");
+        let mut service = baseline_service();
+        apply_env_overrides(&mut service);
+        assert_eq!(service.git.git_ref, "origin/release");
+    }
+
+    #[test]
+    fn test_git_defaults_read_origin_main() {
+        let git = GitConfig::default();
+        assert_eq!(git.remote, "origin");
+        assert_eq!(git.git_ref, "origin/main");
+        assert_eq!(git.links_path, "links.yaml");
+    }
+
+    #[test]
+    fn test_reload_mode_parses_from_toml() {
+        let raw: RawServiceConfig = toml::from_str("[reload]\nmode = \"watch\"\n").unwrap();
+        assert_eq!(raw.reload.unwrap().mode, Some(ReloadMode::Watch));
+        assert_eq!(ReloadConfig::default().mode, ReloadMode::Git);
+    }
+
+    #[test]
+    fn test_sqlite_section_parses_from_toml() {
+        let raw: RawServiceConfig = toml::from_str(
+            "[reload]\nmode = \"sqlite\"\n[sqlite]\npath = \"/data/links.db\"\ntrigger = \"webhook\"\n",
+        )
+        .unwrap();
+        assert_eq!(raw.reload.unwrap().mode, Some(ReloadMode::Sqlite));
+        let sqlite = raw.sqlite.unwrap();
+        assert_eq!(sqlite.path.as_deref(), Some("/data/links.db"));
+        assert_eq!(sqlite.trigger, Some(SqliteTrigger::Webhook));
+        assert_eq!(sqlite.table, None);
+        assert_eq!(SqliteConfig::default().table, "links");
+        assert_eq!(SqliteConfig::default().trigger, SqliteTrigger::Mtime);
+    }
+
+    #[test]
+    fn test_h2c_and_tls_section_parse_from_toml() {
+        let raw: RawServiceConfig = toml::from_str(
+            "h2c = true\n[tls]\naddress = \"0.0.0.0:8443\"\ncert_path = \"/tls/cert.pem\"\nkey_path = \"/tls/key.pem\"\ntls13_only = true\n",
+        )
+        .unwrap();
+        assert_eq!(raw.h2c, Some(true));
+        let tls = raw.tls.unwrap();
+        assert_eq!(tls.address.as_deref(), Some("0.0.0.0:8443"));
+        assert_eq!(tls.cert_path.as_deref(), Some("/tls/cert.pem"));
+        assert_eq!(tls.key_path.as_deref(), Some("/tls/key.pem"));
+        assert_eq!(tls.tls13_only, Some(true));
+        assert_eq!(tls.redirect_http, None);
+        assert!(!TlsConfig::default().redirect_http);
+        assert_eq!(TlsConfig::default().reload_check_secs, 60);
+    }
+
+    #[test]
+    fn test_listeners_parse_from_toml() {
+        let raw: RawServiceConfig = toml::from_str(
+            r#"
+[[listeners]]
+address = "0.0.0.0:8080"
+routes = ["public"]
+
+[[listeners]]
+kind = "unix"
+path = "/run/redirective/admin.sock"
+mode = 0o660
+owner = "redirective"
+group = "adm"
+routes = ["metrics", "reload", "api"]
+
+[[listeners]]
+kind = "systemd"
+name = "web"
+h2c = true
+"#,
+        )
+        .unwrap();
+        let listeners = raw.listeners.unwrap();
+        assert_eq!(listeners.len(), 3);
+        assert_eq!(listeners[0].kind, ListenerKind::Tcp);
+        assert_eq!(listeners[0].address.as_deref(), Some("0.0.0.0:8080"));
+        assert_eq!(listeners[0].routes, vec![RouteGroup::Public]);
+        assert_eq!(listeners[1].kind, ListenerKind::Unix);
+        assert_eq!(listeners[1].mode, Some(0o660));
+        assert_eq!(listeners[1].owner.as_deref(), Some("redirective"));
+        assert_eq!(
+            listeners[1].routes,
+            vec![RouteGroup::Metrics, RouteGroup::Reload, RouteGroup::Api]
+        );
+        assert_eq!(listeners[2].kind, ListenerKind::Systemd);
+        assert_eq!(listeners[2].name.as_deref(), Some("web"));
+        assert!(listeners[2].routes.is_empty());
+        assert!(listeners[2].h2c);
+        assert!(
+            toml::from_str::<RawServiceConfig>("[[listeners]]\nroutes = [\"admin\"]\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_cluster_section_parses_from_toml() {
+        let raw: RawServiceConfig = toml::from_str(
+            "[cluster]\nsecret = \"s3cret\"\nreplay_window_secs = 60\nsync = \"snapshot\"\nnode_id = \"01\"\nleader_election = true\n",
+        )
+        .unwrap();
+        let cluster = raw.cluster.unwrap();
+        assert_eq!(cluster.secret.as_deref(), Some("s3cret"));
+        assert_eq!(cluster.replay_window_secs, Some(60));
+        assert_eq!(cluster.divergence_threshold_secs, None);
+        assert_eq!(cluster.sync, Some(PeerSync::Snapshot));
+        assert_eq!(cluster.node_id.as_deref(), Some("01"));
+        assert_eq!(cluster.leader_election, Some(true));
+        assert!(!ClusterConfig::default().leader_election);
+        assert_eq!(ClusterConfig::default().leader_timeout_secs, 30);
+        assert_eq!(ClusterConfig::default().sync, PeerSync::Relay);
+        assert_eq!(ClusterConfig::default().replay_window_secs, 300);
+        assert_eq!(ClusterConfig::default().divergence_threshold_secs, 300);
+    }
+
+    #[test]
+    fn test_cluster_secret_env_sets_and_clears() {
+        let _lock = env_lock().lock().unwrap();
+        let mut service = baseline_service();
+        {
+            let _guard = EnvGuard::set("REDIRECTIVE_CLUSTER_SECRET", "s3cret");
+            apply_env_overrides(&mut service);
+        }
+        assert_eq!(service.cluster.secret.as_deref(), Some("s3cret"));
+        let _guard = EnvGuard::set("REDIRECTIVE_CLUSTER_SECRET", "");
+        apply_env_overrides(&mut service);
+        assert_eq!(service.cluster.secret, None);
+    }
+
+    #[test]
+    fn test_node_id_env_sets_and_clears() {
+        let _lock = env_lock().lock().unwrap();
+        let mut service = baseline_service();
+        {
+            let _guard = EnvGuard::set("REDIRECTIVE_NODE_ID", "corellia");
+            apply_env_overrides(&mut service);
+        }
+        assert_eq!(service.cluster.node_id.as_deref(), Some("corellia"));
+        let _guard = EnvGuard::set("REDIRECTIVE_NODE_ID", "");
+        apply_env_overrides(&mut service);
+        assert_eq!(service.cluster.node_id, None);
+    }
+
+    #[test]
+    fn test_admin_address_env_sets_and_clears() {
+        let _lock = env_lock().lock().unwrap();
+        let mut service = baseline_service();
+        {
+            let _guard = EnvGuard::set("REDIRECTIVE_ADMIN_ADDRESS", "127.0.0.1:9090");
+            apply_env_overrides(&mut service);
+        }
+        assert_eq!(service.admin_address.as_deref(), Some("127.0.0.1:9090"));
+        let _guard = EnvGuard::set("REDIRECTIVE_ADMIN_ADDRESS", "");
+        apply_env_overrides(&mut service);
+        assert_eq!(service.admin_address, None);
+    }
+
+    #[test]
+    fn test_api_token_env_sets_and_clears() {
+        let _lock = env_lock().lock().unwrap();
+        let mut service = baseline_service();
+        {
+            let _guard = EnvGuard::set("REDIRECTIVE_API_TOKEN", "s3cret");
+            apply_env_overrides(&mut service);
+        }
+        assert_eq!(service.api.token.as_deref(), Some("s3cret"));
+        let _guard = EnvGuard::set("REDIRECTIVE_API_TOKEN", "");
+        apply_env_overrides(&mut service);
+        assert_eq!(service.api.token, None);
+        assert_eq!(service.api.author_name, "redirective");
+    }
+
+    #[test]
+    fn test_remote_section_parses_from_toml() {
+        let raw: RawServiceConfig = toml::from_str(
+            "[reload]\nmode = \"http\"\n[remote]\nurl = \"https://artifacts/links.json\"\n",
+        )
+        .unwrap();
+        assert_eq!(raw.reload.unwrap().mode, Some(ReloadMode::Http));
+        let remote = raw.remote.unwrap();
+        assert_eq!(remote.url.as_deref(), Some("https://artifacts/links.json"));
+        assert_eq!(remote.timeout_secs, None);
+        assert_eq!(RemoteConfig::default().timeout_secs, 10);
+    }
+
+    #[test]
+    fn test_parse_links_rejects_empty_url() {
+        let err = parse_links("foo: https://example.com\nbar: \"  \"\n").unwrap_err();
+        assert!(matches!(err, Error::Config(msg) if msg.contains("bar")));
+    }
 }
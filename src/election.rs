@@ -0,0 +1,291 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! election module: elects one node of the cluster to poll the link source,
+//! so the fleet doesn't all hit the remote. Nodes heartbeat each other's
+//! `/cluster/node`; the leader is the lowest node ID among this node and the
+//! peers heard from recently, so when the leader goes quiet the next-lowest
+//! takes over, and a node that hears from no one polls on its own. Until
+//! its first heartbeat finishes a node doesn't lead, so a fleet starting
+//! together doesn't all poll at once.
+
+use crate::cluster::{self, NODE_ROUTE};
+use crate::config::ClusterConfig;
+use crate::metrics::Metrics;
+use crate::reload::unix_now;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+/// Heartbeat state for this node's view of the election.
+#[derive(Clone)]
+pub struct LeaderElection {
+    node_id: String,
+    /// The peers' `/cluster/node` URLs.
+    peers: Vec<String>,
+    timeout: Duration,
+    /// When each peer node ID last answered a heartbeat, in Unix seconds.
+    seen: Arc<Mutex<HashMap<String, u64>>>,
+    /// The leader as of the last heartbeat, to log changes.
+    last_leader: Arc<Mutex<Option<String>>>,
+    /// A heartbeat has finished. Before that this node doesn't lead, unless
+    /// a whole timeout has passed since `started` (Unix seconds).
+    heard: Arc<AtomicBool>,
+    started: u64,
+    metrics: Metrics,
+}
+
+impl LeaderElection {
+    /// Elect among this node (`node_id`) and the peers behind `peer_urls`
+    /// (webhook URLs; their `/cluster/node` is queried on the same host).
+    pub fn new(node_id: String, peer_urls: &[String], timeout: Duration, metrics: Metrics) -> Self {
+        let peers = peer_urls
+            .iter()
+            .filter_map(|url| {
+                let node = cluster::peer_route(url, NODE_ROUTE);
+                if node.is_none() {
+                    tracing::warn!(peer = %url, "peer URL has no host; not in the election");
+                }
+                node
+            })
+            .collect();
+        LeaderElection {
+            node_id,
+            peers,
+            timeout,
+            seen: Arc::new(Mutex::new(HashMap::new())),
+            last_leader: Arc::new(Mutex::new(None)),
+            heard: Arc::new(AtomicBool::new(false)),
+            started: unix_now(),
+            metrics,
+        }
+    }
+
+    /// The election configured by `cluster`, if enabled. Needs a node ID
+    /// (warned about and disabled otherwise) and peers to elect among.
+    pub fn from_config(
+        cluster: &ClusterConfig,
+        peer_urls: &[String],
+        metrics: &Metrics,
+    ) -> Option<Self> {
+        if !cluster.leader_election {
+            return None;
+        }
+        let Some(node_id) = cluster.node_id.clone() else {
+            tracing::warn!("leader_election needs a node_id; every node polls on its own");
+            return None;
+        };
+        if peer_urls.is_empty() {
+            tracing::info!("leader_election without peers; this node polls on its own");
+            return None;
+        }
+        Some(LeaderElection::new(
+            node_id,
+            peer_urls,
+            Duration::from_secs(cluster.leader_timeout_secs),
+            metrics.clone(),
+        ))
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Note that the peer with `node_id` answered at `now`.
+    fn observe(&self, node_id: String, now: u64) {
+        if node_id == self.node_id {
+            tracing::warn!(node_id, "a peer has this node's ID; both may lead");
+            return;
+        }
+        self.seen.lock().unwrap().insert(node_id, now);
+    }
+
+    /// The leader as of `now`: the lowest ID among this node and the peers
+    /// heard from within the timeout.
+    pub fn leader(&self, now: u64) -> String {
+        let seen = self.seen.lock().unwrap();
+        seen.iter()
+            .filter(|(_, at)| now.saturating_sub(**at) <= self.timeout.as_secs())
+            .map(|(id, _)| id.as_str())
+            .chain([self.node_id.as_str()])
+            .min()
+            .expect("this node is always a candidate")
+            .to_string()
+    }
+
+    /// Whether this node currently leads.
+    pub fn is_leader(&self) -> bool {
+        self.leads(unix_now())
+    }
+
+    /// Whether this node leads as of `now`; never before its first heartbeat
+    /// has finished, for up to one timeout after starting.
+    fn leads(&self, now: u64) -> bool {
+        let settled = self.heard.load(Ordering::Relaxed)
+            || now.saturating_sub(self.started) > self.timeout.as_secs();
+        settled && self.leader(now) == self.node_id
+    }
+
+    /// Query every peer concurrently, noting the node IDs that answer, then
+    /// update the leader gauge (and log if the leader changed).
+    pub async fn heartbeat(&self) {
+        let queries: Vec<_> = self
+            .peers
+            .iter()
+            .map(|url| {
+                let url = url.clone();
+                task::spawn(async move { cluster::fetch_node_state(&url).await })
+            })
+            .collect();
+        for query in queries {
+            // Peers that don't take part in the election report no ID.
+            if let Ok(Ok(state)) = query.await
+                && let Some(node_id) = state.node_id
+            {
+                self.observe(node_id, unix_now());
+            }
+        }
+        self.heard.store(true, Ordering::Relaxed);
+        self.record(unix_now());
+    }
+
+    /// Mirror the leader as of `now` into the gauge and log changes.
+    fn record(&self, now: u64) {
+        let leader = self.leader(now);
+        self.metrics
+            .cluster_leader
+            .set((leader == self.node_id) as i64);
+        let mut last = self.last_leader.lock().unwrap();
+        if last.as_deref() != Some(leader.as_str()) {
+            tracing::info!(leader = %leader, node_id = %self.node_id, "cluster leader elected");
+            *last = Some(leader);
+        }
+    }
+}
+
+/// Heartbeat the peers every `interval` in the background, starting now so
+/// the election settles before the first poll.
+pub fn spawn_heartbeat_task(election: LeaderElection, interval: Duration) {
+    tracing::info!(
+        node_id = %election.node_id,
+        peers = election.peers.len(),
+        interval_secs = interval.as_secs(),
+        "leader election enabled"
+    );
+    task::spawn(async move {
+        loop {
+            election.heartbeat().await;
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::init_metrics;
+
+    fn election(node_id: &str) -> LeaderElection {
+        LeaderElection::new(
+            node_id.to_string(),
+            &[],
+            Duration::from_secs(30),
+            init_metrics(),
+        )
+    }
+
+    #[test]
+    fn test_lowest_recently_heard_id_leads() {
+        let node = election("02");
+        assert_eq!(node.leader(1_000), "02");
+        node.observe("03".to_string(), 1_000);
+        assert_eq!(node.leader(1_000), "02");
+        node.observe("01".to_string(), 1_000);
+        assert_eq!(node.leader(1_000), "01");
+        node.record(1_000);
+        assert_eq!(node.metrics.cluster_leader.get(), 0);
+    }
+
+    #[test]
+    fn test_silent_leader_is_replaced_after_timeout() {
+        let node = election("02");
+        node.observe("01".to_string(), 1_000);
+        assert_eq!(node.leader(1_030), "01");
+        // Nothing heard from 01 for longer than the timeout.
+        assert_eq!(node.leader(1_031), "02");
+        node.record(1_031);
+        assert_eq!(node.metrics.cluster_leader.get(), 1);
+        // It is followed again once it answers.
+        node.observe("01".to_string(), 1_040);
+        assert_eq!(node.leader(1_040), "01");
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_follows_lower_id_peer() {
+        let app = axum::Router::new().route(
+            NODE_ROUTE,
+            axum::routing::get(|| async {
+                axum::Json(cluster::NodeState {
+                    hash: String::new(),
+                    head: None,
+                    link_count: 0,
+                    node_id: Some("01".to_string()),
+                })
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        let peers = vec![format!("http://{}/git-webhook", addr)];
+        let node = LeaderElection::new(
+            "02".to_string(),
+            &peers,
+            Duration::from_secs(30),
+            init_metrics(),
+        );
+        node.heartbeat().await;
+        assert!(!node.is_leader());
+        assert_eq!(node.metrics.cluster_leader.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_no_lead_before_first_heartbeat() {
+        let node = election("01");
+        assert!(!node.leads(node.started));
+        assert!(!node.leads(node.started + 30));
+        // A heartbeat that never finishes doesn't keep the fleet leaderless.
+        assert!(node.leads(node.started + 31));
+        node.heartbeat().await;
+        assert!(node.leads(node.started));
+        assert!(node.is_leader());
+    }
+
+    #[test]
+    fn test_peer_with_same_id_is_ignored() {
+        let node = election("02");
+        node.observe("02".to_string(), 1_000);
+        assert!(node.seen.lock().unwrap().is_empty());
+        assert_eq!(node.leader(1_000), "02");
+    }
+
+    #[test]
+    fn test_from_config_needs_node_id_and_peers() {
+        let metrics = init_metrics();
+        let peers = vec!["http://127.0.0.1:9/git-webhook".to_string()];
+        let mut cluster = ClusterConfig {
+            leader_election: true,
+            ..ClusterConfig::default()
+        };
+        assert!(LeaderElection::from_config(&cluster, &peers, &metrics).is_none());
+        cluster.node_id = Some("01".to_string());
+        assert!(LeaderElection::from_config(&cluster, &[], &metrics).is_none());
+        assert!(LeaderElection::from_config(&cluster, &peers, &metrics).is_some());
+        cluster.leader_election = false;
+        assert!(LeaderElection::from_config(&cluster, &peers, &metrics).is_none());
+    }
+}
@@ -23,6 +23,27 @@ pub enum Error {
     #[error("HTTP server error: {0}")]
     Http(#[from] HyperError),
 
+    #[error("Git error: {0}")]
+    Git(String),
+
+    #[error("Untrusted commit {commit}: {reason}")]
+    UntrustedCommit { commit: String, reason: String },
+
+    #[error("File watch error: {0}")]
+    Watch(#[from] notify::Error),
+
+    #[error("Remote source error: {0}")]
+    Remote(String),
+
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("Reload task ended without a result")]
+    ReloadAborted,
+
+    #[error("TLS error: {0}")]
+    Tls(String),
+
     #[error("Config error: {0}")]
     Config(String),
 
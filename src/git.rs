@@ -0,0 +1,296 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! git module: fetches the links repository and reads files straight out of
+//! a ref's tree, so reloads never touch (or race) a working tree checkout.
+//! Writes (see the `api` module) likewise build commits with plumbing
+//! commands and push them, without a checkout.
+
+use crate::config::GitConfig;
+use crate::errors::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Path to the git binary used for reload fetches.
+pub const GIT_BINARY: &str = "/usr/bin/git";
+
+/// Run `git <args>` in the configured repository, returning trimmed stdout on
+/// success or an `Error::Git` carrying stderr on failure.
+fn run(git_binary: &str, git: &GitConfig, args: &[&str]) -> Result<String, Error> {
+    run_with(git_binary, git, args, &[], None)
+}
+
+/// `run`, with extra environment variables and optionally `stdin` piped to
+/// git.
+fn run_with(
+    git_binary: &str,
+    git: &GitConfig,
+    args: &[&str],
+    envs: &[(&str, &str)],
+    stdin: Option<&str>,
+) -> Result<String, Error> {
+    run_raw(git_binary, git, args, envs, stdin).map(|s| s.trim_end().to_string())
+}
+
+/// `run_with`, returning stdout untrimmed (file contents keep their final
+/// newline).
+fn run_raw(
+    git_binary: &str,
+    git: &GitConfig,
+    args: &[&str],
+    envs: &[(&str, &str)],
+    stdin: Option<&str>,
+) -> Result<String, Error> {
+    let spawn_error =
+        |e: std::io::Error| Error::Git(format!("failed to run git {}: {}", args.join(" "), e));
+    let mut child = Command::new(git_binary)
+        .current_dir(&git.repo_dir)
+        .args(args)
+        .envs(envs.iter().copied())
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error)?;
+    if let Some(input) = stdin {
+        // Dropping the handle closes git's stdin once the input is written.
+        let mut pipe = child.stdin.take().expect("stdin is piped");
+        pipe.write_all(input.as_bytes()).map_err(spawn_error)?;
+    }
+    let output = child.wait_with_output().map_err(spawn_error)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Git(format!(
+            "git {} exited with {}: {}",
+            args.join(" "),
+            output.status,
+            stderr.trim()
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|e| Error::Git(format!("git {} produced non-UTF-8 output: {}", args[0], e)))
+}
+
+/// `git fetch` the configured remote, updating its remote-tracking refs.
+/// Works the same in a bare repository and in a regular clone.
+pub fn fetch(git_binary: &str, git: &GitConfig) -> Result<(), Error> {
+    run(git_binary, git, &["fetch", "--quiet", &git.remote]).map(|_| ())
+}
+
+/// Resolve the configured ref to a full commit hash.
+pub fn resolve_ref(git_binary: &str, git: &GitConfig) -> Result<String, Error> {
+    let spec = format!("{}^{{commit}}", git.git_ref);
+    run(
+        git_binary,
+        git,
+        &["rev-parse", "--verify", "--quiet", &spec],
+    )
+    .map_err(|_| {
+        Error::Git(format!(
+            "ref '{}' does not resolve to a commit",
+            git.git_ref
+        ))
+    })
+}
+
+/// Read the links file out of `commit`'s tree. Reading by commit hash (rather
+/// than by ref name) guarantees the blob matches the commit we resolved, even
+/// if a concurrent fetch moves the ref in between.
+pub fn read_links_blob(git_binary: &str, git: &GitConfig, commit: &str) -> Result<String, Error> {
+    let spec = format!("{}:{}", commit, git.links_path);
+    run_raw(git_binary, git, &["cat-file", "blob", &spec], &[], None)
+}
+
+/// Normalize a signing key fingerprint for comparison. SSH fingerprints
+/// (`SHA256:<base64>`) are case-sensitive; GPG fingerprints are hex and are
+/// often written in spaced groups, so whitespace and case are ignored.
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    let trimmed = fingerprint.trim();
+    if trimmed.starts_with("SHA256:") {
+        trimmed.to_string()
+    } else {
+        trimmed
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_uppercase()
+    }
+}
+
+/// Decide whether a commit signature satisfies the trust policy.
+///
+/// `status` is git's `%G?` code. `G` (good) and `U` (good, but the signer's
+/// validity is unknown to gpg / no principal in an allowed-signers file) both
+/// mean the signature verified cryptographically; trust comes from the
+/// signing key (`%GF`) or its primary key (`%GP`) being in `trusted_keys`.
+fn signature_trusted(status: &str, fingerprints: &[&str], trusted_keys: &[String]) -> bool {
+    if status != "G" && status != "U" {
+        return false;
+    }
+    fingerprints
+        .iter()
+        .filter(|fp| !fp.trim().is_empty())
+        .map(|fp| normalize_fingerprint(fp))
+        .any(|fp| trusted_keys.iter().any(|k| normalize_fingerprint(k) == fp))
+}
+
+/// Refuse `commit` unless it is signed by one of the configured
+/// `trusted_signing_keys`. An empty key list disables the policy.
+///
+/// SSH signatures are checked against an empty allowed-signers file so git
+/// verifies them cryptographically without needing its own trust config;
+/// GPG signing keys must already be in the keyring of the user running
+/// redirective.
+pub fn verify_commit_signature(
+    git_binary: &str,
+    git: &GitConfig,
+    commit: &str,
+) -> Result<(), Error> {
+    if git.trusted_signing_keys.is_empty() {
+        return Ok(());
+    }
+    let report = run(
+        git_binary,
+        git,
+        &[
+            "-c",
+            "gpg.ssh.allowedSignersFile=/dev/null",
+            "log",
+            "-1",
+            "--format=%G?%n%GF%n%GP",
+            commit,
+        ],
+    )?;
+    let mut lines = report.lines();
+    let status = lines.next().unwrap_or("N").trim();
+    let fingerprints: Vec<&str> = lines.collect();
+    if signature_trusted(status, &fingerprints, &git.trusted_signing_keys) {
+        return Ok(());
+    }
+    let reason = match status {
+        "N" => "commit is not signed".to_string(),
+        "G" | "U" => format!(
+            "signed by untrusted key {}",
+            fingerprints.first().copied().unwrap_or("?").trim()
+        ),
+        other => format!("signature could not be verified (git status '{}')", other),
+    };
+    Err(Error::UntrustedCommit {
+        commit: commit.to_string(),
+        reason,
+    })
+}
+
+/// Resolve the configured ref, enforce the signing policy on its commit, and
+/// read the links file from it, returning the commit hash alongside the file
+/// contents.
+pub fn read_links_at_ref(git_binary: &str, git: &GitConfig) -> Result<(String, String), Error> {
+    let commit = resolve_ref(git_binary, git)?;
+    verify_commit_signature(git_binary, git, &commit)?;
+    let content = read_links_blob(git_binary, git, &commit)?;
+    Ok((commit, content))
+}
+
+/// Commit `content` as the links file on top of `parent`, without touching
+/// any checkout: the blob is written straight to the object store and the
+/// tree is built in a throwaway index. Returns the new commit's hash; it is
+/// not on any branch until pushed (see `push`).
+pub fn commit_links(
+    git_binary: &str,
+    git: &GitConfig,
+    parent: &str,
+    content: &str,
+    message: &str,
+    author_name: &str,
+    author_email: &str,
+) -> Result<String, Error> {
+    static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+    let blob = run_with(
+        git_binary,
+        git,
+        &["hash-object", "-w", "--stdin"],
+        &[],
+        Some(content),
+    )?;
+    let index = std::env::temp_dir().join(format!(
+        "redirective-index-{}-{}",
+        std::process::id(),
+        NEXT_INDEX.fetch_add(1, Ordering::Relaxed)
+    ));
+    let index_path = index.to_string_lossy().into_owned();
+    let index_env = [("GIT_INDEX_FILE", index_path.as_str())];
+    let cacheinfo = format!("100644,{},{}", blob, git.links_path);
+    let tree = run_with(git_binary, git, &["read-tree", parent], &index_env, None)
+        .and_then(|_| {
+            run_with(
+                git_binary,
+                git,
+                &["update-index", "--add", "--cacheinfo", &cacheinfo],
+                &index_env,
+                None,
+            )
+        })
+        .and_then(|_| run_with(git_binary, git, &["write-tree"], &index_env, None));
+    let _ = std::fs::remove_file(&index);
+    let tree = tree?;
+    let identity = [
+        ("GIT_AUTHOR_NAME", author_name),
+        ("GIT_AUTHOR_EMAIL", author_email),
+        ("GIT_COMMITTER_NAME", author_name),
+        ("GIT_COMMITTER_EMAIL", author_email),
+    ];
+    run_with(
+        git_binary,
+        git,
+        &["commit-tree", &tree, "-p", parent, "-m", message],
+        &identity,
+        None,
+    )
+}
+
+/// Push `commit` to `branch` on the configured remote. Only fast-forwards are
+/// accepted, so a commit built on a stale parent is rejected rather than
+/// overwriting someone else's push.
+pub fn push(git_binary: &str, git: &GitConfig, commit: &str, branch: &str) -> Result<(), Error> {
+    let refspec = format!("{}:refs/heads/{}", commit, branch);
+    run(git_binary, git, &["push", "--quiet", &git.remote, &refspec]).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SSH_FP: &str = "SHA256:WkuiLJI8+UwZNpzRT2k7PCFmvYwk9PBno2VR/D6lreE";
+    const GPG_FP: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+
+    #[test]
+    fn test_signature_trusted_matches_ssh_fingerprint() {
+        let trusted = vec![SSH_FP.to_string()];
+        assert!(signature_trusted("G", &[SSH_FP, ""], &trusted));
+        assert!(signature_trusted("U", &[SSH_FP, ""], &trusted));
+    }
+
+    #[test]
+    fn test_signature_trusted_gpg_ignores_case_and_spacing() {
+        let trusted = vec!["0123 4567 89ab cdef 0123  4567 89AB CDEF 0123 4567".to_string()];
+        // Signed by a subkey whose primary key is the trusted one.
+        assert!(signature_trusted("G", &["FFFF", GPG_FP], &trusted));
+    }
+
+    #[test]
+    fn test_signature_untrusted_key_rejected() {
+        let trusted = vec![GPG_FP.to_string()];
+        assert!(!signature_trusted("G", &[SSH_FP, ""], &trusted));
+    }
+
+    #[test]
+    fn test_signature_bad_or_missing_rejected_even_for_trusted_key() {
+        let trusted = vec![SSH_FP.to_string()];
+        for status in ["N", "B", "E", "X", "Y", "R"] {
+            assert!(!signature_trusted(status, &[SSH_FP, ""], &trusted));
+        }
+    }
+}
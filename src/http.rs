@@ -1,16 +1,34 @@
 // (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
 //! http module: HTTP server with Axum.
 
+use crate::api;
 use crate::cache::RouterCache;
-use crate::config::{Config, ServiceConfig};
+use crate::cluster::{self, ClusterMonitor, ReplayGuard, SnapshotReceiver};
+use crate::config::{
+    ListenerConfig, PeerSync, ReloadMode, RouteGroup, ServiceConfig, ShutdownConfig, SqliteTrigger,
+};
+use crate::election::{self, LeaderElection};
 use crate::errors::Error;
+use crate::listen::{Socket, SystemdSockets, UnixIncoming};
 use crate::metrics::Metrics;
+use crate::payload::{self, PushFilter, Trigger};
+use crate::relay::{Delivery, RELAY_HEADER, RelayOutbox, RelaySecrets};
+use crate::reload::{
+    ReloadCoordinator, ReloadReport, ReloadStatus, Reloader, SharedResult, spawn_mtime_task,
+    spawn_poll_task, spawn_sighup_task, spawn_watch_task, unix_now,
+};
+use crate::signature;
+use crate::source::LinkSource;
+use crate::tls::{self, CertStore};
 
 /// Run the HTTP server.
 ///
-/// Serves `/healthz`, `/version`, `/metrics`, and `/:code` endpoints.
+/// Serves `/healthz`, `/version`, `/metrics`, `/reload/status`,
+/// `/cluster/node`, `/cluster/status`, `/cluster/snapshot` (with a cluster
+/// secret), the write API under `/api/links/`, and `/:code` endpoints.
 use axum::{
-    Router,
+    Json, Router,
+    body::Bytes,
     extract::{ConnectInfo, Extension, Query},
     http::{HeaderMap, StatusCode, Uri, header},
     response::{IntoResponse, Response},
@@ -18,24 +36,25 @@ use axum::{
 };
 use mime_guess::from_path;
 use prometheus::{Encoder, TextEncoder};
-use serde::Deserialize;
-use std::path::Path as FsPath;
-
-/// Header marking a webhook request as relayed from a peer node.
-/// Requests carrying this header are never relayed again (loop prevention).
-const RELAY_HEADER: &str = "x-redirective-relay";
-
-/// Path to the git binary used for reload pulls.
-const GIT_BINARY: &str = "/usr/bin/git";
+use serde::{Deserialize, Serialize};
+use std::path::{Path as FsPath, PathBuf};
 
-/// Timeout for relaying the webhook to the peer node.
-const RELAY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Header opting a webhook request into synchronous mode (same as `?wait=`).
+const WAIT_HEADER: &str = "x-redirective-wait";
 
 /// Configuration for the reload webhook.
 #[derive(Clone)]
 struct WebhookConfig {
     path: String,
-    peer_url: Option<String>,
+    peer_urls: Vec<String>,
+    sync_timeout: Duration,
+    /// Shared secret requests must be signed with; `None` accepts anything.
+    secret: Option<String>,
+    /// Cluster secret relays are signed with; `None` trusts the bare relay
+    /// header.
+    cluster_secret: Option<String>,
+    /// Which push events trigger a reload.
+    filter: PushFilter,
 }
 
 /// Rate limit information per client IP.
@@ -100,19 +119,22 @@ impl RateLimiter {
         }
     }
 }
-use std::env;
-use std::process::Command;
-
 use std::{
     collections::HashMap,
     net::{IpAddr, SocketAddr},
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
     time::{Duration, Instant},
 };
 
 use tokio::fs;
+use tokio::net::TcpListener;
+use tokio::signal::unix::{Signal, SignalKind, signal};
 use tokio::sync::Mutex as TokioMutex;
-use tokio::task;
+use tokio::sync::{oneshot, watch};
+use tokio::task::{self, JoinSet};
 
 /// Internal application state
 #[derive(Clone)]
@@ -120,58 +142,224 @@ struct AppState {
     cache: RouterCache,
     metrics: Metrics,
     version: String,
-    reload_mutex: Arc<TokioMutex<()>>,
     rate_limiter: Arc<RateLimiter>,
+    replay_guard: Arc<ReplayGuard>,
+    relay_outbox: RelayOutbox,
     webhook_config: WebhookConfig,
+    coordinator: ReloadCoordinator,
+    /// Set once shutdown has begun; `/healthz` then reports "draining".
+    draining: Arc<AtomicBool>,
 }
 
-/// Build the Axum application with routes and shared state. `reload_mutex`
-/// is shared with the background poll task (see `spawn_poll_task`) so the
-/// two reload triggers never race each other's `git pull`.
+/// Build the Axum application's routes, by group, with shared state.
+/// `coordinator` is shared with the background reload tasks (see
+/// `spawn_poll_task`) so webhook, poll and signal triggers never race each
+/// other's `git fetch`.
+/// `draining` is flipped by the shutdown signal handler (see
+/// `wait_for_shutdown`). With peers configured, this also starts the
+/// background cluster consistency checks, and in snapshot sync mode hooks
+/// every reload that changed the table to push the new table to them. With an
+/// `election`, the leader relays every reload that changed the table.
 fn create_app(
-    cache: RouterCache,
-    metrics: Metrics,
+    coordinator: ReloadCoordinator,
     version: String,
     service: ServiceConfig,
-    reload_mutex: Arc<TokioMutex<()>>,
-) -> Router<()> {
+    draining: Arc<AtomicBool>,
+    election: Option<LeaderElection>,
+) -> AppRoutes {
+    let snapshot_sync = match (service.cluster.sync, &service.cluster.secret) {
+        (PeerSync::Snapshot, Some(_)) => true,
+        (PeerSync::Snapshot, None) => {
+            tracing::warn!(
+                "cluster sync = \"snapshot\" needs a cluster secret; relaying webhooks instead"
+            );
+            false
+        }
+        (PeerSync::Relay, _) => false,
+    };
+    let delivery = if snapshot_sync {
+        Delivery::Snapshot(Box::new(coordinator.reloader().clone()))
+    } else {
+        Delivery::Webhook
+    };
+    let relay_outbox = RelayOutbox::new(
+        RelaySecrets {
+            webhook: service.webhook_secret.clone(),
+            cluster: service.cluster.secret.clone(),
+        },
+        delivery,
+        coordinator.reloader().metrics.clone(),
+        Duration::from_secs(service.relay_retry_secs),
+        service.relay_outbox_path.as_ref().map(PathBuf::from),
+    );
+    if snapshot_sync && !service.peer_urls.is_empty() {
+        // Push after every reload that changed the table, whatever triggered
+        // it; the outbox retries a peer that missed one. Tables installed
+        // from a peer's snapshot don't fire this, so pushes never bounce
+        // between nodes.
+        let outbox = relay_outbox.clone();
+        let peers = service.peer_urls.clone();
+        coordinator.reloader().after_reload(move |outcome| {
+            if outcome.changed {
+                for peer in &peers {
+                    outbox.enqueue(peer.clone());
+                }
+            }
+        });
+    } else if let Some(election) = election.clone() {
+        // Only the leader polls, so it passes on what its polls pick up.
+        // Followers' reloads aren't passed on, which keeps relays from
+        // bouncing around the cluster.
+        let outbox = relay_outbox.clone();
+        let peers = service.peer_urls.clone();
+        coordinator.reloader().after_reload(move |outcome| {
+            if outcome.changed && election.is_leader() {
+                for peer in &peers {
+                    outbox.enqueue(peer.clone());
+                }
+            }
+        });
+    }
+    let replay_guard = Arc::new(ReplayGuard::new(service.cluster.replay_window_secs));
+    let snapshot_receiver = service
+        .cluster
+        .secret
+        .clone()
+        .map(|secret| SnapshotReceiver::new(coordinator.clone(), secret, replay_guard.clone()));
     let state = AppState {
-        cache,
-        metrics,
+        cache: coordinator.reloader().cache.clone(),
+        metrics: coordinator.reloader().metrics.clone(),
+        coordinator,
         version,
-        reload_mutex,
+        draining,
         rate_limiter: Arc::new(RateLimiter::new(
             service.rate_limit_per_minute,
             service.rate_limit_per_day,
         )),
+        replay_guard,
+        relay_outbox,
         webhook_config: WebhookConfig {
             path: service.webhook_path.clone(),
-            peer_url: service.peer_url.clone(),
+            // In snapshot mode peers get the table from the reload hook, not
+            // a relayed webhook
+            peer_urls: if snapshot_sync {
+                Vec::new()
+            } else {
+                service.peer_urls.clone()
+            },
+            sync_timeout: Duration::from_secs(service.webhook_sync_timeout_secs),
+            secret: service.webhook_secret.clone(),
+            cluster_secret: service.cluster.secret.clone(),
+            filter: PushFilter {
+                branch: match &service.webhook_branch {
+                    Some(branch) if branch.is_empty() => None,
+                    Some(branch) => Some(branch.clone()),
+                    None => payload::tracked_branch(&service.git),
+                },
+                paths: service.webhook_paths.clone(),
+            },
         },
     };
-    let mut router = Router::new()
+    let public = Router::new()
         .route("/healthz", get(healthz_handler))
         .route("/version", get(version_handler))
         .route("/available", get(available_handler))
-        .route("/metrics", get(metrics_handler));
+        // Fallback to handle redirects, static files, or SPA index.html
+        .fallback(spa_handler);
+    let metrics = Router::new().route("/metrics", get(metrics_handler));
 
-    // Webhook endpoint to trigger reload (POST) and reject other methods (405)
-    router = router
+    // Webhook endpoint to trigger reload (POST) and reject other methods
+    // (405), and the reload status
+    let reload = Router::new()
+        .route("/reload/status", get(reload_status_handler))
         .route(&state.webhook_config.path, post(webhook_handler))
         .route(
             &state.webhook_config.path,
             get(|| async { StatusCode::METHOD_NOT_ALLOWED }),
         );
 
-    router
-        // Fallback to handle redirects, static files, or SPA index.html
-        .fallback(spa_handler)
-        .layer(Extension(state))
+    // Write API, when a token is configured (git mode only). Its changes
+    // are relayed like a webhook's, unless a reload hook already passes
+    // every change on
+    let mut api = Router::new();
+    let api_relay_targets = if snapshot_sync || election.is_some() {
+        Vec::new()
+    } else {
+        service.peer_urls.clone()
+    };
+    if service.reload.mode == ReloadMode::Git
+        && let Some(routes) = api::routes(
+            state.coordinator.clone(),
+            &service.git,
+            &service.api,
+            state.relay_outbox.clone(),
+            api_relay_targets,
+        )
+    {
+        api = routes;
+    }
+
+    // Cluster consistency: our table's hash for peers, and the comparison
+    // with theirs (also run periodically to keep the divergence gauges fresh);
+    // plus the snapshot endpoint when a cluster secret is set
+    let monitor = ClusterMonitor::new(
+        state.coordinator.reloader().clone(),
+        election.map(|election| election.node_id().to_string()),
+        &service.peer_urls,
+        Duration::from_secs(service.cluster.divergence_threshold_secs),
+    );
+    cluster::spawn_check_task(
+        monitor.clone(),
+        Duration::from_secs(service.cluster.check_interval_secs),
+    );
+    let cluster = cluster::routes(monitor, snapshot_receiver);
+
+    let groups = [
+        (RouteGroup::Public, public),
+        (RouteGroup::Metrics, metrics),
+        (RouteGroup::Reload, reload),
+        (RouteGroup::Api, api),
+        (RouteGroup::Cluster, cluster),
+    ];
+    AppRoutes {
+        groups: groups
+            .into_iter()
+            .map(|(group, router)| (group, router.layer(Extension(state.clone()))))
+            .collect(),
+    }
+}
+
+/// The app's routes by `RouteGroup`, so each listener can serve a subset of
+/// them. Clones share the app's state.
+#[derive(Clone)]
+struct AppRoutes {
+    groups: HashMap<RouteGroup, Router<()>>,
+}
+
+impl AppRoutes {
+    /// A router serving just `groups`.
+    fn router(&self, groups: &[RouteGroup]) -> Router<()> {
+        RouteGroup::ALL
+            .iter()
+            .filter(|group| groups.contains(group))
+            .filter_map(|group| self.groups.get(group))
+            .fold(Router::new(), |router, group| router.merge(group.clone()))
+    }
+
+    /// Serve `router` in place of `group`'s routes.
+    fn replace(&mut self, group: RouteGroup, router: Router<()>) {
+        self.groups.insert(group, router);
+    }
 }
 
-/// Health check endpoint.
-async fn healthz_handler() -> &'static str {
-    "OK"
+/// Health check endpoint. Reports 503 "draining" once shutdown has begun so
+/// load balancers stop routing here before the listener closes.
+async fn healthz_handler(Extension(state): Extension<AppState>) -> impl IntoResponse {
+    if state.draining.load(Ordering::Relaxed) {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    } else {
+        (StatusCode::OK, "OK")
+    }
 }
 
 /// Version endpoint.
@@ -190,6 +378,13 @@ async fn metrics_handler(Extension(state): Extension<AppState>) -> impl IntoResp
     ([(header::CONTENT_TYPE, content_type)], buffer)
 }
 
+/// Reload status endpoint: last attempt/success times, last error, served
+/// git HEAD and link count, and whether a reload is running, so CI can poll
+/// until its push is live.
+async fn reload_status_handler(Extension(state): Extension<AppState>) -> Json<ReloadReport> {
+    Json(state.coordinator.reloader().status.report())
+}
+
 // root_handler removed; spa_handler fallback handles static files and index.html
 
 /// Available endpoint: tells whether a shortcode is unused.
@@ -328,242 +523,833 @@ fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap) -> IpAddr {
     peer
 }
 
-/// Decide whether this webhook should be relayed to a peer.
+/// Decide which peers this webhook should be relayed to.
 ///
-/// Returns the peer URL to relay to when `peer_url` is configured and the
-/// incoming request did not itself arrive via relay (no `X-Redirective-Relay`
-/// header). A relayed request is never relayed again, so two nodes pointing
-/// at each other cannot loop.
-fn relay_target(peer_url: Option<&str>, headers: &HeaderMap) -> Option<String> {
-    match peer_url {
-        Some(url) if !headers.contains_key(RELAY_HEADER) => Some(url.to_string()),
-        _ => None,
-    }
-}
-
-/// Fire a relay POST to the peer's webhook endpoint, marking it with the
-/// relay-guard header. Failures are logged and counted but never propagate.
-async fn relay_to_peer(peer_url: &str, metrics: &Metrics) {
-    let request = hyper::Request::builder()
-        .method(hyper::Method::POST)
-        .uri(peer_url)
-        .header(RELAY_HEADER, "1")
-        .body(hyper::Body::empty());
-    let request = match request {
-        Ok(req) => req,
-        Err(e) => {
-            tracing::error!(peer = peer_url, error = %e, "invalid peer_url; relay skipped");
-            metrics.relay_fail.inc();
-            return;
-        }
+/// Returns the configured `peer_urls` when the incoming request did not
+/// itself arrive via relay (no `X-Redirective-Relay` header), else nothing.
+/// A relayed request is never relayed again, so nodes pointing at each
+/// other cannot loop.
+fn relay_targets(peer_urls: &[String], headers: &HeaderMap) -> Vec<String> {
+    if headers.contains_key(RELAY_HEADER) {
+        Vec::new()
+    } else {
+        peer_urls.to_vec()
+    }
+}
+
+/// Reload the link table, then (only on a successful reload) queue a relay
+/// of the webhook to every peer in `relay_targets`. Peers are relayed to
+/// concurrently and retried in the background (see `RelayOutbox`), so relay
+/// failures never delay or affect the reload outcome, which is returned for
+/// synchronous webhook callers.
+///
+/// Only the first relaying webhook attached to a run relays it, whether or
+/// not that webhook started the run: one relay per run is enough for the
+/// peers to catch up.
+async fn reload_and_relay(
+    coordinator: &ReloadCoordinator,
+    relay_targets: Vec<String>,
+    outbox: &RelayOutbox,
+) -> SharedResult {
+    let ticket = if relay_targets.is_empty() {
+        coordinator.trigger()
+    } else {
+        coordinator.trigger_relaying()
     };
-    let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new());
-    match tokio::time::timeout(RELAY_TIMEOUT, client.request(request)).await {
-        Ok(Ok(resp)) if resp.status().is_success() => {
-            tracing::info!(peer = peer_url, status = %resp.status(), "relayed webhook to peer");
-            metrics.relay_success.inc();
-        }
-        Ok(Ok(resp)) => {
-            tracing::warn!(peer = peer_url, status = %resp.status(), "peer relay rejected");
-            metrics.relay_fail.inc();
-        }
-        Ok(Err(e)) => {
-            tracing::warn!(peer = peer_url, error = %e, "peer relay failed");
-            metrics.relay_fail.inc();
-        }
-        Err(_) => {
-            tracing::warn!(
-                peer = peer_url,
-                timeout_secs = RELAY_TIMEOUT.as_secs(),
-                "peer relay timed out"
-            );
-            metrics.relay_fail.inc();
+    let relay = ticket.relay;
+    let result = ticket.wait().await;
+    if result.is_ok() && relay {
+        for peer_url in relay_targets {
+            outbox.enqueue(peer_url);
         }
     }
+    result
 }
 
-/// Run `git rev-parse HEAD` in `repo_dir`, returning the trimmed commit hash
-/// on success. Used only to detect whether a pull actually moved HEAD.
-fn git_head(git_binary: &str, repo_dir: &std::path::Path) -> Option<String> {
-    Command::new(git_binary)
-        .current_dir(repo_dir)
-        .args(["rev-parse", "HEAD"])
-        .output()
-        .ok()
-        .filter(|o| o.status.success())
-        .and_then(|o| String::from_utf8(o.stdout).ok())
-        .map(|s| s.trim().to_string())
+/// Wait for SIGTERM or SIGINT, then flip `/healthz` to "draining" and keep
+/// serving for `pre_drain` so load balancers notice before the listeners
+/// stop accepting. Returns when the drain (and the drain timeout) begins.
+async fn wait_for_shutdown(
+    mut terminate: Signal,
+    mut interrupt: Signal,
+    draining: Arc<AtomicBool>,
+    pre_drain: Duration,
+) {
+    let name = tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = interrupt.recv() => "SIGINT",
+    };
+    draining.store(true, Ordering::Relaxed);
+    tracing::info!(
+        signal = name,
+        pre_drain_secs = pre_drain.as_secs(),
+        "shutdown requested; /healthz now reports draining"
+    );
+    tokio::time::sleep(pre_drain).await;
 }
 
-/// Run `git pull --ff-only` and, on success, reload the link table from
-/// `links.yaml` into `cache`.
-///
-/// Returns `Some(changed)` on a successful reload, where `changed` reports
-/// whether the pull actually moved HEAD (useful for quiet-steady-state
-/// logging), or `None` if the pull or the reload failed. Metrics are
-/// incremented here so every caller gets consistent accounting.
-async fn reload_links(cache: &RouterCache, metrics: &Metrics, git_binary: &str) -> Option<bool> {
-    let repo_dir = env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-
-    let before = git_head(git_binary, &repo_dir);
-
-    let pulled = Command::new(git_binary)
-        .current_dir(&repo_dir)
-        .args(["pull", "--ff-only"])
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false);
-    if !pulled {
-        metrics.reload_fail.inc();
-        return None;
-    }
-
-    let cfg = match Config::load("links.yaml") {
-        Ok(cfg) => cfg,
-        Err(_) => {
-            metrics.reload_fail.inc();
-            return None;
-        }
-    };
+/// Query parameters accepted by the webhook.
+#[derive(Deserialize)]
+struct WebhookParams {
+    /// Opt into synchronous mode; see `wants_sync`.
+    wait: Option<String>,
+}
 
-    let mut codes: Vec<String> = cfg.links.keys().cloned().collect();
-    codes.sort();
-    let _ = std::fs::write("static_html/shortcodes.txt", codes.join("\n"));
-    cache.swap(cfg.links);
-    metrics.reload_success.inc();
+/// Interpret a `wait` flag: present and empty, or any of `1`/`true`/`yes`.
+fn truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "" | "1" | "true" | "yes"
+    )
+}
 
-    let after = git_head(git_binary, &repo_dir);
-    Some(before.is_some() && after != before)
+/// Returns true if the caller asked to wait for the reload outcome, via
+/// `?wait=true` or an `X-Redirective-Wait: true` header.
+fn wants_sync(params: &WebhookParams, headers: &HeaderMap) -> bool {
+    params.wait.as_deref().is_some_and(truthy)
+        || headers
+            .get(WAIT_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(truthy)
 }
 
-/// Run a git pull and reload the link table, then (only on a successful
-/// reload) relay the webhook to `relay_target`, if any. Relay failures do
-/// not affect the reload outcome.
-async fn reload_and_relay(
-    cache: RouterCache,
-    metrics: Metrics,
-    relay_target: Option<String>,
-    git_binary: &str,
-) {
-    if reload_links(&cache, &metrics, git_binary).await.is_some()
-        && let Some(peer_url) = relay_target
-    {
-        relay_to_peer(&peer_url, &metrics).await;
-    }
+/// JSON body for a webhook event that did not trigger a reload.
+#[derive(Serialize)]
+struct WebhookIgnored {
+    ignored: String,
 }
 
-/// Convert a configured poll interval into a `Duration`, treating `None` or
-/// `0` as "polling disabled". Kept side-effect free so it can be unit
-/// tested without touching tokio's clock.
-fn poll_interval(poll_interval_secs: Option<u64>) -> Option<Duration> {
-    match poll_interval_secs {
-        None | Some(0) => None,
-        Some(secs) => Some(Duration::from_secs(secs)),
-    }
+/// JSON body for a failed synchronous reload.
+#[derive(Serialize)]
+struct ReloadErrorBody {
+    error: String,
 }
 
-/// Spawn the background git-poll reload task, if `poll_interval_secs`
-/// enables it. Shares `reload_mutex` with the webhook handler so a poll and
-/// a webhook-triggered reload never run `git pull` concurrently.
-fn spawn_poll_task(
-    cache: RouterCache,
-    metrics: Metrics,
-    reload_mutex: Arc<TokioMutex<()>>,
-    poll_interval_secs: Option<u64>,
-) {
-    let Some(interval) = poll_interval(poll_interval_secs) else {
-        tracing::info!("git-poll reload disabled (poll_interval_secs unset or 0)");
-        return;
-    };
-    tracing::info!(
-        interval_secs = interval.as_secs(),
-        "git-poll reload enabled"
-    );
-    task::spawn(async move {
-        let mut ticker = tokio::time::interval(interval);
-        // The first tick fires immediately; links are already fresh from
-        // startup, so skip it and only reload on subsequent ticks.
-        ticker.tick().await;
-        loop {
-            ticker.tick().await;
-            let _guard = reload_mutex.lock().await;
-            match reload_links(&cache, &metrics, GIT_BINARY).await {
-                Some(true) => tracing::info!("git-poll reload: links changed"),
-                Some(false) => {}
-                None => tracing::warn!("git-poll reload: pull or reload failed"),
-            }
+/// Map a failed reload to the status a synchronous webhook caller sees:
+/// 422 when the links (or their signature) were rejected, 502 when git
+/// itself failed, 500 otherwise.
+fn reload_error_status(error: &Error) -> StatusCode {
+    match error {
+        Error::Config(_) | Error::YamlParse(_) | Error::UntrustedCommit { .. } => {
+            StatusCode::UNPROCESSABLE_ENTITY
         }
-    });
+        Error::Git(_) | Error::Remote(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
 }
 
-/// Webhook endpoint to trigger a git pull and reload.
+/// Webhook endpoint to trigger a reload (a git fetch, or a re-read of the
+/// links file in watch mode).
+///
+/// By default the reload runs in the background and the caller gets 202
+/// immediately. With `?wait=true` (or `X-Redirective-Wait: true`) the request
+/// is held until the reload finishes: 200 with the new HEAD and diff counts,
+/// or an error status with the reason (see `reload_error_status`). If that
+/// takes longer than the sync timeout the caller gets 504, but the reload
+/// itself still runs to completion.
+///
+/// When a webhook secret is configured, requests whose signature does not
+/// verify against the raw body get 401 and never reach the reload path.
+/// When a cluster secret is configured, so do relays (requests carrying
+/// `X-Redirective-Relay`) without a valid relay signature, or whose signature
+/// is stale or was already used; a relay that passes needs no webhook
+/// signature.
+/// Forge push payloads for other branches (or, if configured, not touching
+/// the links files) and non-push events get 200 without a reload; pings get
+/// 200 "pong". Anything else, e.g. a bare `curl -X POST`, reloads.
 async fn webhook_handler(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Extension(state): Extension<AppState>,
+    Query(params): Query<WebhookParams>,
     headers: HeaderMap,
+    body: Bytes,
 ) -> impl IntoResponse {
     let ip = resolve_client_ip(addr.ip(), &headers);
     if !state.rate_limiter.allow(ip).await {
         return StatusCode::TOO_MANY_REQUESTS.into_response();
     }
-    let relay_target = relay_target(state.webhook_config.peer_url.as_deref(), &headers);
-    let reload_mutex = state.reload_mutex.clone();
-    let cache = state.cache.clone();
-    let metrics = state.metrics.clone();
+    let mut trusted_relay = false;
+    if headers.contains_key(RELAY_HEADER)
+        && let Some(cluster_secret) = state.webhook_config.cluster_secret.as_deref()
+    {
+        let now = unix_now();
+        let window = state.replay_guard.window_secs;
+        let fresh = match signature::verify_relay(cluster_secret, &headers, &body, now, window) {
+            Some(stamp) => state.replay_guard.first_use(stamp, now).await,
+            None => false,
+        };
+        if !fresh {
+            tracing::warn!(client = %ip, "rejected relay with missing, invalid or replayed signature");
+            state.metrics.webhook_unauthorized.inc();
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        trusted_relay = true;
+    }
+    if !trusted_relay
+        && let Some(secret) = state.webhook_config.secret.as_deref()
+        && !signature::verify_webhook(secret, &headers, &body)
+    {
+        tracing::warn!(client = %ip, "rejected webhook with missing or invalid signature");
+        state.metrics.webhook_unauthorized.inc();
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match payload::classify(&headers, &body, &state.webhook_config.filter) {
+        Trigger::Reload => {}
+        Trigger::Ping => return (StatusCode::OK, "pong").into_response(),
+        Trigger::Ignore(reason) => {
+            tracing::info!(reason = %reason, "webhook ignored");
+            return (StatusCode::OK, Json(WebhookIgnored { ignored: reason })).into_response();
+        }
+    }
+    let relay_targets = relay_targets(&state.webhook_config.peer_urls, &headers);
+    let outbox = state.relay_outbox.clone();
+    let coordinator = state.coordinator.clone();
+    let (done_tx, done_rx) = oneshot::channel();
     task::spawn(async move {
-        let _guard = reload_mutex.lock().await;
-        reload_and_relay(cache, metrics, relay_target, GIT_BINARY).await;
+        let result = reload_and_relay(&coordinator, relay_targets, &outbox).await;
+        let _ = done_tx.send(result);
     });
-    StatusCode::ACCEPTED.into_response()
+    if !wants_sync(&params, &headers) {
+        return StatusCode::ACCEPTED.into_response();
+    }
+    match tokio::time::timeout(state.webhook_config.sync_timeout, done_rx).await {
+        Ok(Ok(Ok(outcome))) => (StatusCode::OK, Json(outcome)).into_response(),
+        Ok(Ok(Err(e))) => (
+            reload_error_status(&e),
+            Json(ReloadErrorBody {
+                error: e.to_string(),
+            }),
+        )
+            .into_response(),
+        Ok(Err(_)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ReloadErrorBody {
+                error: Error::ReloadAborted.to_string(),
+            }),
+        )
+            .into_response(),
+        Err(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(ReloadErrorBody {
+                error: format!(
+                    "reload still running after {}s; check /reload/status",
+                    state.webhook_config.sync_timeout.as_secs()
+                ),
+            }),
+        )
+            .into_response(),
+    }
 }
 
-/// Run the HTTP server.
+/// Run the HTTP server. Reloads refresh the table from `source`; `head` is
+/// the revision the startup table came from, if any, and seeds
+/// `/reload/status`.
 pub async fn run_http_server(
     cache: RouterCache,
     metrics: Metrics,
     service: ServiceConfig,
+    source: Arc<dyn LinkSource>,
+    head: Option<String>,
 ) -> Result<(), Error> {
+    api::check_format(&service.git, &service.reload, &service.api)?;
     let version = env!("CARGO_PKG_VERSION").to_string();
-    let reload_mutex = Arc::new(TokioMutex::new(()));
-    spawn_poll_task(
-        cache.clone(),
-        metrics.clone(),
-        reload_mutex.clone(),
-        service.poll_interval_secs,
+    let status = ReloadStatus::new(metrics.clone(), cache.snapshot().len(), head);
+    let reloader = Reloader::new(cache, metrics, status, source);
+    let coordinator = ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(())));
+    let election = LeaderElection::from_config(
+        &service.cluster,
+        &service.peer_urls,
+        &coordinator.reloader().metrics,
     );
-    let app = create_app(cache, metrics, version, service.clone(), reload_mutex);
-    let addr: SocketAddr = service.address.parse()?;
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
-        .await?;
+    if let Some(election) = &election {
+        election::spawn_heartbeat_task(
+            election.clone(),
+            Duration::from_secs(service.cluster.heartbeat_interval_secs),
+        );
+    }
+    match service.reload.mode {
+        ReloadMode::Git | ReloadMode::Http => spawn_poll_task(
+            if service.reload.mode == ReloadMode::Git {
+                "git"
+            } else {
+                "http"
+            },
+            coordinator.clone(),
+            service.poll_interval_secs,
+            service.poll_max_backoff_secs,
+            service.poll_failure_alert_threshold,
+            election.clone(),
+        ),
+        ReloadMode::Watch => spawn_watch_task(coordinator.clone(), &service.reload)?,
+        ReloadMode::Sqlite => match service.sqlite.trigger {
+            SqliteTrigger::Mtime => spawn_mtime_task(coordinator.clone(), &service.sqlite),
+            SqliteTrigger::Poll => spawn_poll_task(
+                "sqlite",
+                coordinator.clone(),
+                service.poll_interval_secs,
+                service.poll_max_backoff_secs,
+                service.poll_failure_alert_threshold,
+                // A local database is cheap to poll; no need to elect
+                None,
+            ),
+            SqliteTrigger::Webhook => {
+                tracing::info!("sqlite reloads on webhook and SIGHUP only")
+            }
+        },
+    }
+    spawn_sighup_task(coordinator.clone())?;
+    let draining = Arc::new(AtomicBool::new(false));
+    let app = create_app(
+        coordinator,
+        version,
+        service.clone(),
+        draining.clone(),
+        election,
+    );
+    let mut plain_app = app.clone();
+    let mut tls_listener = None;
+    if let (Some(certs), Some(tls_address)) =
+        (CertStore::from_config(&service.tls)?, &service.tls.address)
+    {
+        let tls_addr: SocketAddr = tls_address.parse()?;
+        tls::spawn_reload_task(
+            certs.clone(),
+            Duration::from_secs(service.tls.reload_check_secs),
+        );
+        if service.tls.redirect_http {
+            // Other route groups on the plain listeners are left as they are
+            plain_app.replace(RouteGroup::Public, tls::redirect_router(tls_addr.port()));
+        }
+        tls_listener = Some(Listener {
+            socket: Socket::Tcp(std::net::TcpListener::bind(tls_addr)?),
+            app: app.router(default_routes(&service)),
+            tls: Some(certs),
+            h2c: false,
+        });
+    }
+    for peer in peers_on_public_port(&service) {
+        tracing::warn!(
+            peer = %peer,
+            "peer URL targets a public-only port; relays, /cluster and leader election need the admin port"
+        );
+    }
+    let mut systemd = SystemdSockets::from_env();
+    let mut listeners = Vec::new();
+    for config in plain_listeners(&service) {
+        listeners.push(Listener {
+            socket: Socket::open(&config, &mut systemd)?,
+            app: plain_app.router(&config.routes),
+            tls: None,
+            h2c: config.h2c,
+        });
+    }
+    listeners.extend(tls_listener);
+    serve_until_shutdown(listeners, draining, &service.shutdown).await
+}
+
+/// The route groups a listener serves unless configured otherwise: all of
+/// them, or just the public ones when the rest are on the admin listener.
+fn default_routes(service: &ServiceConfig) -> &'static [RouteGroup] {
+    if service.admin_address.is_some() {
+        &[RouteGroup::Public]
+    } else {
+        RouteGroup::ALL
+    }
+}
+
+/// The plain (non-TLS) listeners to serve, with their route groups filled
+/// in: `listeners`, or else `address`, plus the admin listener if set.
+fn plain_listeners(service: &ServiceConfig) -> Vec<ListenerConfig> {
+    let mut configs = if service.listeners.is_empty() {
+        vec![ListenerConfig {
+            address: Some(service.address.clone()),
+            h2c: service.h2c,
+            ..ListenerConfig::default()
+        }]
+    } else {
+        service.listeners.clone()
+    };
+    for config in &mut configs {
+        if config.routes.is_empty() {
+            config.routes = default_routes(service).to_vec();
+        }
+    }
+    if let Some(admin_address) = &service.admin_address {
+        configs.push(ListenerConfig {
+            address: Some(admin_address.clone()),
+            routes: RouteGroup::ADMIN.to_vec(),
+            ..ListenerConfig::default()
+        });
+    }
+    configs
+}
+
+/// The `peer_urls` whose port is one of this node's TCP listeners without
+/// the reload and cluster routes (with `admin_address` set, the public
+/// port). Peers are assumed to listen like this node, so relays and
+/// election queries sent there would miss.
+fn peers_on_public_port(service: &ServiceConfig) -> Vec<&str> {
+    let mut addresses: Vec<&str> = Vec::new();
+    let listeners = plain_listeners(service);
+    for config in &listeners {
+        if !config.routes.contains(&RouteGroup::Reload)
+            && let Some(address) = &config.address
+        {
+            addresses.push(address);
+        }
+    }
+    if !default_routes(service).contains(&RouteGroup::Reload)
+        && let Some(address) = &service.tls.address
+    {
+        addresses.push(address);
+    }
+    let ports: Vec<u16> = addresses
+        .iter()
+        .filter_map(|address| address.rsplit_once(':')?.1.parse().ok())
+        .collect();
+    service
+        .peer_urls
+        .iter()
+        .filter(|url| {
+            url.parse::<hyper::Uri>().is_ok_and(|uri| {
+                let default = if uri.scheme_str() == Some("https") {
+                    443
+                } else {
+                    80
+                };
+                ports.contains(&uri.port_u16().unwrap_or(default))
+            })
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+/// A bound socket and the app to serve there.
+struct Listener {
+    socket: Socket,
+    app: Router<()>,
+    /// Terminate TLS with this certificate (TCP sockets only). HTTP/2 is
+    /// offered via ALPN.
+    tls: Option<CertStore>,
+    /// Accept cleartext HTTP/2 with prior knowledge as well as HTTP/1 (plain
+    /// listeners only).
+    h2c: bool,
+}
+
+/// Serve `listener` as a task in `servers` until `stop` is set.
+fn spawn_server(
+    listener: Listener,
+    mut stop: watch::Receiver<bool>,
+    servers: &mut JoinSet<Result<(), hyper::Error>>,
+) -> Result<(), Error> {
+    let stopped = async move {
+        let _ = stop.wait_for(|stopped| *stopped).await;
+    };
+    let described = listener.socket.describe();
+    let app = listener
+        .app
+        .into_make_service_with_connect_info::<SocketAddr>();
+    match (listener.socket, listener.tls) {
+        (Socket::Tcp(tcp), None) => {
+            // hyper tells HTTP/2 from HTTP/1 by the connection preface
+            let server = axum::Server::from_tcp(tcp)?
+                .http1_only(!listener.h2c)
+                .serve(app);
+            tracing::info!(addr = %described, h2c = listener.h2c, "listening (HTTP)");
+            servers.spawn(server.with_graceful_shutdown(stopped));
+        }
+        (Socket::Tcp(tcp), Some(certs)) => {
+            tcp.set_nonblocking(true)?;
+            let server = axum::Server::builder(tls::incoming(TcpListener::from_std(tcp)?, certs))
+                .serve(app)
+                .with_graceful_shutdown(stopped);
+            tracing::info!(addr = %described, "listening (HTTPS)");
+            servers.spawn(server);
+        }
+        (Socket::Unix(unix, _), _) => {
+            let server = axum::Server::builder(UnixIncoming::new(unix)?)
+                .http1_only(!listener.h2c)
+                .serve(app);
+            tracing::info!(addr = %described, h2c = listener.h2c, "listening (HTTP)");
+            servers.spawn(server.with_graceful_shutdown(stopped));
+        }
+    }
+    Ok(())
+}
+
+/// Serve every listener until SIGTERM/SIGINT, then drain gracefully: stop
+/// accepting, let in-flight requests finish, and give up after the drain
+/// timeout rather than hanging shutdown on a stuck connection.
+async fn serve_until_shutdown(
+    listeners: Vec<Listener>,
+    draining: Arc<AtomicBool>,
+    shutdown: &ShutdownConfig,
+) -> Result<(), Error> {
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        spawn_server(listener, stop_rx.clone(), &mut servers)?;
+    }
+    let signalled = wait_for_shutdown(
+        signal(SignalKind::terminate())?,
+        signal(SignalKind::interrupt())?,
+        draining,
+        Duration::from_secs(shutdown.pre_drain_secs),
+    );
+    tokio::select! {
+        // A listener only stops by itself if it fails
+        Some(result) = servers.join_next() => {
+            result.map_err(|e| Error::Other(e.to_string()))??;
+        }
+        _ = signalled => {
+            let _ = stop_tx.send(true);
+            let drain_timeout = Duration::from_secs(shutdown.drain_timeout_secs);
+            let drained = tokio::time::timeout(drain_timeout, async {
+                while let Some(result) = servers.join_next().await {
+                    result.map_err(|e| Error::Other(e.to_string()))??;
+                }
+                Ok::<_, Error>(())
+            });
+            match drained.await {
+                Ok(result) => result?,
+                Err(_) => tracing::warn!(
+                    drain_timeout_secs = shutdown.drain_timeout_secs,
+                    "drain timeout elapsed; exiting with requests still in flight"
+                ),
+            }
+        }
+    }
+    tracing::info!("server shut down");
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{
+        ApiConfig, ClusterConfig, GitConfig, LinkFormat, ReloadConfig, RemoteConfig, SqliteConfig,
+        TlsConfig,
+    };
     use crate::metrics::init_metrics;
+    use crate::reload::testing::GitFixture;
+    use crate::source::GitSource;
+    use crate::tls::testing::{CertFiles, connect};
     use axum::body::Body;
     use axum::http::Request;
     use axum::http::StatusCode;
     use hyper::body::to_bytes;
     use std::collections::HashMap;
+    use tokio_rustls::rustls::version::TLS13;
     use tower::ServiceExt;
 
     // Default ServiceConfig for tests
     fn default_service() -> ServiceConfig {
         ServiceConfig {
             address: "127.0.0.1:0".to_string(),
+            listeners: Vec::new(),
+            admin_address: None,
+            h2c: false,
             webhook_path: "/git-webhook".to_string(),
+            webhook_sync_timeout_secs: 60,
+            webhook_secret: None,
+            webhook_branch: None,
+            webhook_paths: Vec::new(),
             rate_limit_per_minute: 1,
             rate_limit_per_day: 100,
-            peer_url: None,
+            peer_urls: Vec::new(),
+            relay_retry_secs: 300,
+            relay_outbox_path: None,
             poll_interval_secs: None,
+            poll_max_backoff_secs: 900,
+            poll_failure_alert_threshold: 5,
+            git: GitConfig::default(),
+            reload: ReloadConfig::default(),
+            sqlite: SqliteConfig::default(),
+            remote: RemoteConfig::default(),
+            api: ApiConfig::default(),
+            cluster: ClusterConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            tls: TlsConfig::default(),
+        }
+    }
+
+    fn coordinator(reloader: Reloader) -> ReloadCoordinator {
+        ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(())))
+    }
+
+    /// A reloader over `cache` that is never asked to reload.
+    fn idle_reloader(cache: RouterCache, metrics: Metrics) -> Reloader {
+        let status = ReloadStatus::new(metrics.clone(), cache.snapshot().len(), None);
+        let source = GitSource::new(GitConfig::default(), LinkFormat::Yaml);
+        Reloader::new(cache, metrics, status, Arc::new(source))
+    }
+
+    /// A reloader whose every reload fails in `git fetch`.
+    fn failing_reloader(cache: RouterCache, metrics: Metrics) -> Reloader {
+        let status = ReloadStatus::new(metrics.clone(), cache.snapshot().len(), None);
+        // /usr/bin/false stands in for a git fetch that fails.
+        let source = GitSource::new(GitConfig::default(), LinkFormat::Yaml)
+            .with_git_binary("/usr/bin/false");
+        Reloader::new(cache, metrics, status, Arc::new(source))
+    }
+
+    /// A listener served the way `run_http_server` serves it; stops when
+    /// dropped.
+    struct TestServer {
+        _stop: watch::Sender<bool>,
+        _servers: JoinSet<Result<(), hyper::Error>>,
+    }
+
+    /// Serve the `routes` of an app redirecting `gh` on `socket`.
+    fn serve_test_app(
+        socket: Socket,
+        routes: &[RouteGroup],
+        tls: Option<CertStore>,
+        h2c: bool,
+    ) -> TestServer {
+        let links = HashMap::from([("gh".to_string(), "https://github.com".to_string())]);
+        let app = create_app(
+            coordinator(idle_reloader(RouterCache::new(links), init_metrics())),
+            "1.0".to_string(),
+            default_service(),
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(routes);
+        let listener = Listener {
+            socket,
+            app,
+            tls,
+            h2c,
+        };
+        let (stop, stop_rx) = watch::channel(false);
+        let mut servers = JoinSet::new();
+        spawn_server(listener, stop_rx, &mut servers).unwrap();
+        TestServer {
+            _stop: stop,
+            _servers: servers,
+        }
+    }
+
+    /// Serve the whole app on a local port.
+    fn spawn_test_server(tls: Option<CertStore>, h2c: bool) -> (SocketAddr, TestServer) {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let server = serve_test_app(Socket::Tcp(tcp), RouteGroup::ALL, tls, h2c);
+        (addr, server)
+    }
+
+    /// Send `GET path` over `io` with HTTP/2 (`http2`) or HTTP/1.1.
+    async fn get_over<IO>(io: IO, http2: bool, path: &str) -> hyper::Result<Response>
+    where
+        IO: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        let (mut sender, conn) = hyper::client::conn::Builder::new()
+            .http2_only(http2)
+            .handshake(io)
+            .await?;
+        tokio::spawn(conn);
+        let request = Request::get(format!("http://localhost{}", path))
+            .header(header::HOST, "localhost")
+            .body(Body::empty())
+            .unwrap();
+        sender
+            .send_request(request)
+            .await
+            .map(|response| response.map(axum::body::boxed))
+    }
+
+    /// The redirect and a static file both come back over `version`.
+    async fn assert_serves(responses: [Response; 2], version: axum::http::Version) {
+        let [redirect, file] = responses;
+        assert_eq!(redirect.version(), version);
+        assert_eq!(redirect.status(), StatusCode::FOUND);
+        assert_eq!(redirect.headers()[header::LOCATION], "https://github.com");
+        assert_eq!(file.version(), version);
+        assert_eq!(file.status(), StatusCode::OK);
+        assert_eq!(file.headers()[header::CONTENT_TYPE], "text/css");
+        let body = to_bytes(file.into_body()).await.unwrap();
+        assert_eq!(
+            &body[..],
+            &std::fs::read("static_html/styles.css").unwrap()[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_h2c_listener_serves_http2_and_http1() {
+        let (addr, _server) = spawn_test_server(None, true);
+        for (http2, version) in [
+            (true, axum::http::Version::HTTP_2),
+            (false, axum::http::Version::HTTP_11),
+        ] {
+            let mut responses = Vec::new();
+            for path in ["/gh", "/styles.css"] {
+                let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+                responses.push(get_over(tcp, http2, path).await.unwrap());
+            }
+            assert_serves(responses.try_into().unwrap(), version).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plain_listener_refuses_h2c_unless_enabled() {
+        let (addr, _server) = spawn_test_server(None, false);
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        assert!(get_over(tcp, true, "/gh").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tls_listener_negotiates_http2_or_http1() {
+        let files = CertFiles::new();
+        let certs = CertStore::load(&files.cert_path, &files.key_path, false).unwrap();
+        let (addr, _server) = spawn_test_server(Some(certs), false);
+        for (alpn, version) in [
+            (&b"h2"[..], axum::http::Version::HTTP_2),
+            (&b"http/1.1"[..], axum::http::Version::HTTP_11),
+        ] {
+            let mut responses = Vec::new();
+            for path in ["/gh", "/styles.css"] {
+                let tls = connect(addr, &files.cert_path, &[alpn], &[&TLS13])
+                    .await
+                    .unwrap();
+                assert_eq!(tls.get_ref().1.alpn_protocol(), Some(alpn));
+                let http2 = version == axum::http::Version::HTTP_2;
+                responses.push(get_over(tls, http2, path).await.unwrap());
+            }
+            assert_serves(responses.try_into().unwrap(), version).await;
         }
     }
 
-    fn new_reload_mutex() -> Arc<TokioMutex<()>> {
-        Arc::new(TokioMutex::new(()))
+    #[tokio::test]
+    async fn test_unix_listener_serves_only_its_route_groups() {
+        let dir = std::env::temp_dir().join(format!(
+            "redirective-http-{}-{}",
+            std::process::id(),
+            fastrand::u64(..)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("admin.sock");
+        let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let _server = serve_test_app(
+            Socket::Unix(unix, None),
+            &[RouteGroup::Metrics, RouteGroup::Reload],
+            None,
+            false,
+        );
+        let mut statuses = Vec::new();
+        for path_and_query in ["/metrics", "/reload/status", "/gh", "/healthz"] {
+            let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+            let response = get_over(stream, false, path_and_query).await.unwrap();
+            statuses.push(response.status());
+        }
+        assert_eq!(
+            statuses,
+            [
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+                StatusCode::NOT_FOUND
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_public_routes_leave_metrics_to_shortcodes() {
+        let links = HashMap::from([(
+            "metrics".to_string(),
+            "https://grafana.example.com".to_string(),
+        )]);
+        let app = create_app(
+            coordinator(idle_reloader(RouterCache::new(links), init_metrics())),
+            "1.0".to_string(),
+            default_service(),
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(&[RouteGroup::Public]);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/metrics")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://grafana.example.com"
+        );
+    }
+
+    #[test]
+    fn test_peers_on_public_port_flags_peers_missing_admin_routes() {
+        let mut service = default_service();
+        service.address = "0.0.0.0:8080".to_string();
+        service.peer_urls = vec![
+            "http://peer-a:8080/git-webhook".to_string(),
+            "http://peer-b:9090/git-webhook".to_string(),
+        ];
+        assert!(peers_on_public_port(&service).is_empty());
+        service.admin_address = Some("0.0.0.0:9090".to_string());
+        assert_eq!(
+            peers_on_public_port(&service),
+            ["http://peer-a:8080/git-webhook"]
+        );
+        service.address = "0.0.0.0:80".to_string();
+        service.peer_urls = vec!["http://peer-a/git-webhook".to_string()];
+        assert_eq!(
+            peers_on_public_port(&service),
+            ["http://peer-a/git-webhook"]
+        );
+    }
+
+    #[test]
+    fn test_admin_address_moves_all_but_public_routes() {
+        let mut service = default_service();
+        let listeners = plain_listeners(&service);
+        assert_eq!(listeners.len(), 1);
+        assert_eq!(listeners[0].routes, RouteGroup::ALL);
+
+        service.admin_address = Some("127.0.0.1:9090".to_string());
+        let listeners = plain_listeners(&service);
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[0].address.as_deref(), Some("127.0.0.1:0"));
+        assert_eq!(listeners[0].routes, [RouteGroup::Public]);
+        assert_eq!(listeners[1].address.as_deref(), Some("127.0.0.1:9090"));
+        assert_eq!(listeners[1].routes, RouteGroup::ADMIN);
+
+        // Routes set on a listener are kept as they are
+        service.listeners = vec![ListenerConfig {
+            address: Some("127.0.0.1:8081".to_string()),
+            routes: vec![RouteGroup::Public, RouteGroup::Reload],
+            ..ListenerConfig::default()
+        }];
+        let listeners = plain_listeners(&service);
+        assert_eq!(
+            listeners[0].routes,
+            [RouteGroup::Public, RouteGroup::Reload]
+        );
+        assert_eq!(listeners[1].routes, RouteGroup::ADMIN);
+    }
+
+    #[test]
+    fn test_reload_error_status_by_cause() {
+        let status = |e: Error| reload_error_status(&e);
+        assert_eq!(
+            status(Error::Config("bad links".to_string())),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(Error::Git("fetch failed".to_string())),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            status(Error::ReloadAborted),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
     }
 
     #[tokio::test]
@@ -571,12 +1357,13 @@ mod tests {
         let cache = RouterCache::new(HashMap::new());
         let metrics = init_metrics();
         let app = create_app(
-            cache,
-            metrics,
+            coordinator(idle_reloader(cache, metrics)),
             "1.2.3".to_string(),
             default_service(),
-            new_reload_mutex(),
-        );
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(RouteGroup::ALL);
         let response = app
             .clone()
             .oneshot(
@@ -592,17 +1379,79 @@ mod tests {
         assert_eq!(&body[..], b"OK");
     }
 
+    #[tokio::test]
+    async fn test_healthz_reports_draining_after_shutdown_begins() {
+        let draining = Arc::new(AtomicBool::new(false));
+        let app = create_app(
+            coordinator(idle_reloader(
+                RouterCache::new(HashMap::new()),
+                init_metrics(),
+            )),
+            "1.2.3".to_string(),
+            default_service(),
+            draining.clone(),
+            None,
+        )
+        .router(RouteGroup::ALL);
+        draining.store(true, Ordering::Relaxed);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/healthz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"draining");
+    }
+
+    #[tokio::test]
+    async fn test_reload_status_reports_served_table() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics);
+        let head = reloader.reload().await.unwrap().head.unwrap();
+        let app = create_app(
+            coordinator(reloader),
+            "1.0".to_string(),
+            default_service(),
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(RouteGroup::ALL);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/reload/status")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(&format!("\"head\":\"{}\"", head)));
+        assert!(body.contains("\"link_count\":1"));
+        assert!(body.contains("\"in_progress\":false"));
+        assert!(body.contains("\"last_error\":null"));
+    }
+
     #[tokio::test]
     async fn test_version() {
         let cache = RouterCache::new(HashMap::new());
         let metrics = init_metrics();
         let app = create_app(
-            cache,
-            metrics,
+            coordinator(idle_reloader(cache, metrics)),
             "vX.Y".to_string(),
             default_service(),
-            new_reload_mutex(),
-        );
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(RouteGroup::ALL);
         let response = app
             .clone()
             .oneshot(
@@ -625,12 +1474,13 @@ mod tests {
         let cache = RouterCache::new(map);
         let metrics = init_metrics();
         let app = create_app(
-            cache,
-            metrics,
+            coordinator(idle_reloader(cache, metrics)),
             "1.0".to_string(),
             default_service(),
-            new_reload_mutex(),
-        );
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(RouteGroup::ALL);
         let response = app
             .clone()
             .oneshot(
@@ -653,12 +1503,13 @@ mod tests {
         let cache = RouterCache::new(map);
         let metrics = init_metrics();
         let app = create_app(
-            cache,
-            metrics,
+            coordinator(idle_reloader(cache, metrics)),
             "1.0".to_string(),
             default_service(),
-            new_reload_mutex(),
-        );
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(RouteGroup::ALL);
         let response = app
             .clone()
             .oneshot(
@@ -700,41 +1551,59 @@ mod tests {
         (format!("http://{}/git-webhook", addr), received)
     }
 
+    /// An outbox that gives up on a relay after its first failed attempt.
+    fn test_outbox(metrics: &Metrics, secrets: RelaySecrets) -> RelayOutbox {
+        RelayOutbox::new(
+            secrets,
+            Delivery::Webhook,
+            metrics.clone(),
+            Duration::ZERO,
+            None,
+        )
+    }
+
     #[test]
-    fn test_relay_target_set_when_peer_configured_and_no_guard() {
+    fn test_relay_targets_set_when_peers_configured_and_no_guard() {
         let headers = HeaderMap::new();
-        assert_eq!(
-            relay_target(Some("http://peer/git-webhook"), &headers),
-            Some("http://peer/git-webhook".to_string())
-        );
+        let peers = vec![
+            "http://b/git-webhook".to_string(),
+            "http://c/git-webhook".to_string(),
+        ];
+        assert_eq!(relay_targets(&peers, &headers), peers);
     }
 
     #[test]
-    fn test_relay_target_none_when_guard_header_present() {
+    fn test_relay_targets_empty_when_guard_header_present() {
         let mut headers = HeaderMap::new();
         headers.insert(RELAY_HEADER, "1".parse().unwrap());
-        assert_eq!(
-            relay_target(Some("http://peer/git-webhook"), &headers),
-            None
-        );
+        let peers = vec!["http://peer/git-webhook".to_string()];
+        assert!(relay_targets(&peers, &headers).is_empty());
     }
 
     #[test]
-    fn test_relay_target_none_when_peer_absent() {
+    fn test_relay_targets_empty_when_no_peers() {
         let headers = HeaderMap::new();
-        assert_eq!(relay_target(None, &headers), None);
+        assert!(relay_targets(&[], &headers).is_empty());
     }
 
     #[tokio::test]
     async fn test_relay_fires_after_successful_reload() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
         let (peer_url, received) = spawn_mock_peer().await;
         let cache = RouterCache::new(HashMap::new());
         let metrics = init_metrics();
-        // /usr/bin/true stands in for a git pull that succeeds.
-        reload_and_relay(cache, metrics.clone(), Some(peer_url), "/usr/bin/true").await;
+        let reloader = fixture.reloader(cache, metrics.clone());
+        let outbox = test_outbox(&metrics, RelaySecrets::default());
+        reload_and_relay(&coordinator(reloader), vec![peer_url.clone()], &outbox)
+            .await
+            .unwrap();
+        outbox.drained().await;
         assert_eq!(metrics.reload_success.get(), 1);
-        assert_eq!(metrics.relay_success.get(), 1);
-        assert_eq!(metrics.relay_fail.get(), 0);
+        assert_eq!(
+            metrics.relay_success.with_label_values(&[&peer_url]).get(),
+            1
+        );
+        assert_eq!(metrics.relay_fail.with_label_values(&[&peer_url]).get(), 0);
         let requests = received.lock().await;
         assert_eq!(requests.len(), 1);
         // The relayed request must carry the guard header so the peer
@@ -742,14 +1611,48 @@ mod tests {
         assert_eq!(requests[0].get(RELAY_HEADER).unwrap(), "1");
     }
 
+    #[tokio::test]
+    async fn test_webhook_joining_a_poll_queued_run_still_relays() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let (peer_url, received) = spawn_mock_peer().await;
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let outbox = test_outbox(&metrics, RelaySecrets::default());
+        let reload_mutex = Arc::new(TokioMutex::new(()));
+        let coordinator = ReloadCoordinator::new(reloader, reload_mutex.clone());
+        // Polls start a run and queue its follow-up; a webhook and a second
+        // one then join that follow-up.
+        let guard = reload_mutex.lock().await;
+        let running = coordinator.trigger();
+        let queued = coordinator.trigger();
+        let webhook = reload_and_relay(&coordinator, vec![peer_url.clone()], &outbox);
+        let second = reload_and_relay(&coordinator, vec![peer_url.clone()], &outbox);
+        // Both webhooks attach before the runs are released.
+        let (webhook, second, ()) = tokio::join!(webhook, second, async { drop(guard) });
+        webhook.unwrap();
+        second.unwrap();
+        running.wait().await.unwrap();
+        queued.wait().await.unwrap();
+        outbox.drained().await;
+        assert_eq!(metrics.reload_success.get(), 2);
+        assert_eq!(received.lock().await.len(), 1);
+    }
+
     #[tokio::test]
     async fn test_no_relay_when_target_none() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
         let cache = RouterCache::new(HashMap::new());
         let metrics = init_metrics();
-        reload_and_relay(cache, metrics.clone(), None, "/usr/bin/true").await;
+        let reloader = fixture.reloader(cache, metrics.clone());
+        let outbox = test_outbox(&metrics, RelaySecrets::default());
+        reload_and_relay(&coordinator(reloader), Vec::new(), &outbox)
+            .await
+            .unwrap();
         assert_eq!(metrics.reload_success.get(), 1);
-        assert_eq!(metrics.relay_success.get(), 0);
-        assert_eq!(metrics.relay_fail.get(), 0);
+        assert_eq!(metrics.relay_outbox_depth.get(), 0);
+        assert!(metrics.registry.gather().iter().all(|f| {
+            !matches!(f.name(), "relay_success" | "relay_fail") || f.get_metric().is_empty()
+        }));
     }
 
     #[tokio::test]
@@ -757,11 +1660,18 @@ mod tests {
         let (peer_url, received) = spawn_mock_peer().await;
         let cache = RouterCache::new(HashMap::new());
         let metrics = init_metrics();
-        // /usr/bin/false stands in for a git pull that fails.
-        reload_and_relay(cache, metrics.clone(), Some(peer_url), "/usr/bin/false").await;
+        let reloader = failing_reloader(cache, metrics.clone());
+        let outbox = test_outbox(&metrics, RelaySecrets::default());
+        reload_and_relay(&coordinator(reloader), vec![peer_url.clone()], &outbox)
+            .await
+            .unwrap_err();
+        assert_eq!(metrics.relay_outbox_depth.get(), 0);
         assert_eq!(metrics.reload_fail.get(), 1);
-        assert_eq!(metrics.relay_success.get(), 0);
-        assert_eq!(metrics.relay_fail.get(), 0);
+        assert_eq!(
+            metrics.relay_success.with_label_values(&[&peer_url]).get(),
+            0
+        );
+        assert_eq!(metrics.relay_fail.with_label_values(&[&peer_url]).get(), 0);
         assert!(received.lock().await.is_empty());
     }
 
@@ -771,13 +1681,343 @@ mod tests {
         let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
         let addr = listener.local_addr().unwrap();
         drop(listener);
+        let fixture = GitFixture::new("foo: https://example.com\n");
         let cache = RouterCache::new(HashMap::new());
         let metrics = init_metrics();
-        let peer_url = format!("http://{}/git-webhook", addr);
-        reload_and_relay(cache, metrics.clone(), Some(peer_url), "/usr/bin/true").await;
+        let dead_peer = format!("http://{}/git-webhook", addr);
+        let (live_peer, received) = spawn_mock_peer().await;
+        let reloader = fixture.reloader(cache, metrics.clone());
+        let outbox = test_outbox(&metrics, RelaySecrets::default());
+        reload_and_relay(
+            &coordinator(reloader),
+            vec![dead_peer.clone(), live_peer.clone()],
+            &outbox,
+        )
+        .await
+        .unwrap();
+        outbox.drained().await;
         assert_eq!(metrics.reload_success.get(), 1);
-        assert_eq!(metrics.relay_success.get(), 0);
-        assert_eq!(metrics.relay_fail.get(), 1);
+        // Each peer is counted under its own label; the dead one doesn't
+        // stop the live one from being relayed to.
+        assert_eq!(
+            metrics.relay_success.with_label_values(&[&dead_peer]).get(),
+            0
+        );
+        assert_eq!(metrics.relay_fail.with_label_values(&[&dead_peer]).get(), 1);
+        assert_eq!(
+            metrics.relay_success.with_label_values(&[&live_peer]).get(),
+            1
+        );
+        assert_eq!(metrics.relay_fail.with_label_values(&[&live_peer]).get(), 0);
+        assert_eq!(received.lock().await.len(), 1);
+    }
+
+    fn webhook_request(uri: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut builder = Request::builder().method("POST").uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+        request
+    }
+
+    fn webhook_app(reloader: Reloader) -> Router<()> {
+        webhook_app_with_secret(reloader, None)
+    }
+
+    fn webhook_app_with_secret(reloader: Reloader, secret: Option<&str>) -> Router<()> {
+        let mut service = default_service();
+        service.rate_limit_per_minute = 10;
+        service.webhook_secret = secret.map(str::to_string);
+        create_app(
+            coordinator(reloader),
+            "1.0".to_string(),
+            service,
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(RouteGroup::ALL)
+    }
+
+    #[tokio::test]
+    async fn test_webhook_rejects_unsigned_request_when_secret_set() {
+        let metrics = init_metrics();
+        let reloader = idle_reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let app = webhook_app_with_secret(reloader, Some("s3cret"));
+        let response = app
+            .clone()
+            .oneshot(webhook_request("/git-webhook", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let forged = signature::sign("wrong", b"");
+        let response = app
+            .oneshot(webhook_request(
+                "/git-webhook",
+                &[(signature::HUB_SIGNATURE_HEADER, &forged)],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(metrics.webhook_unauthorized.get(), 2);
+        assert_eq!(metrics.reload_success.get() + metrics.reload_fail.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_accepts_signed_request() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let app = webhook_app_with_secret(reloader, Some("s3cret"));
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        let mut request = webhook_request(
+            "/git-webhook?wait=true",
+            &[(
+                signature::HUB_SIGNATURE_HEADER,
+                &signature::sign("s3cret", body),
+            )],
+        );
+        *request.body_mut() = Body::from(&body[..]);
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .oneshot(webhook_request(
+                "/git-webhook?wait=true",
+                &[(signature::GITLAB_TOKEN_HEADER, "s3cret")],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(metrics.webhook_unauthorized.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_relay_is_signed_when_secret_set() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let (peer_url, received) = spawn_mock_peer().await;
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let secrets = RelaySecrets {
+            webhook: Some("s3cret".to_string()),
+            cluster: Some("cluster".to_string()),
+        };
+        let outbox = test_outbox(&metrics, secrets);
+        reload_and_relay(&coordinator(reloader), vec![peer_url], &outbox)
+            .await
+            .unwrap();
+        outbox.drained().await;
+        let requests = received.lock().await;
+        assert_eq!(requests.len(), 1);
+        assert!(signature::verify_webhook("s3cret", &requests[0], b""));
+        assert!(signature::verify_relay("cluster", &requests[0], b"", unix_now(), 60).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_sync_pushes_only_changed_tables() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let coordinator = coordinator(reloader);
+        let mut service = default_service();
+        service.cluster.sync = PeerSync::Snapshot;
+        service.cluster.secret = Some("cluster".to_string());
+        // Nothing listens there, so a push stays pending in the outbox.
+        service.peer_urls = vec!["http://127.0.0.1:9/git-webhook".to_string()];
+        let _app = create_app(
+            coordinator.clone(),
+            "1.0".to_string(),
+            service,
+            Arc::new(AtomicBool::new(false)),
+            None,
+        );
+        let outcome = coordinator.trigger().wait().await.unwrap();
+        assert!(!outcome.changed);
+        assert_eq!(metrics.relay_outbox_depth.get(), 0);
+        fixture.commit("foo: https://example.org\n");
+        coordinator.trigger().wait().await.unwrap();
+        assert_eq!(metrics.relay_outbox_depth.get(), 1);
+    }
+
+    fn cluster_app(reloader: Reloader, webhook_secret: Option<&str>) -> Router<()> {
+        let mut service = default_service();
+        service.rate_limit_per_minute = 10;
+        service.webhook_secret = webhook_secret.map(str::to_string);
+        service.cluster.secret = Some("cluster".to_string());
+        create_app(
+            coordinator(reloader),
+            "1.0".to_string(),
+            service,
+            Arc::new(AtomicBool::new(false)),
+            None,
+        )
+        .router(RouteGroup::ALL)
+    }
+
+    #[tokio::test]
+    async fn test_webhook_rejects_forged_relay_when_cluster_secret_set() {
+        let metrics = init_metrics();
+        let reloader = idle_reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let app = cluster_app(reloader, None);
+        let stale = signature::sign_relay("cluster", unix_now() - 301, "n1", b"");
+        let wrong_key = signature::sign_relay("guess", unix_now(), "n2", b"");
+        for headers in [
+            vec![(RELAY_HEADER, "1")],
+            vec![
+                (RELAY_HEADER, "1"),
+                (signature::RELAY_SIGNATURE_HEADER, &stale),
+            ],
+            vec![
+                (RELAY_HEADER, "1"),
+                (signature::RELAY_SIGNATURE_HEADER, &wrong_key),
+            ],
+        ] {
+            let response = app
+                .clone()
+                .oneshot(webhook_request("/git-webhook", &headers))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(metrics.webhook_unauthorized.get(), 3);
+        assert_eq!(metrics.reload_success.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_accepts_signed_relay_once() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        // The webhook secret isn't needed: the relay signature authenticates.
+        let app = cluster_app(reloader, Some("s3cret"));
+        let value = signature::sign_relay("cluster", unix_now(), "n1", b"");
+        let headers = [
+            (RELAY_HEADER, "1"),
+            (signature::RELAY_SIGNATURE_HEADER, value.as_str()),
+        ];
+        let response = app
+            .clone()
+            .oneshot(webhook_request("/git-webhook?wait=true", &headers))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Replaying the captured request is refused.
+        let response = app
+            .oneshot(webhook_request("/git-webhook?wait=true", &headers))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(metrics.webhook_unauthorized.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_answers_ping_and_ignores_other_branches() {
+        let metrics = init_metrics();
+        let reloader = idle_reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let app = webhook_app(reloader);
+        let response = app
+            .clone()
+            .oneshot(webhook_request(
+                "/git-webhook",
+                &[("x-github-event", "ping")],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut request = webhook_request("/git-webhook", &[("x-github-event", "push")]);
+        *request.body_mut() = Body::from(r#"{"ref":"refs/heads/feature","commits":[]}"#);
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("not for branch main"));
+        // Give a (wrongly) spawned reload a chance to run before checking.
+        tokio::task::yield_now().await;
+        assert_eq!(metrics.reload_success.get() + metrics.reload_fail.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_defaults_to_fire_and_forget() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), init_metrics());
+        let response = webhook_app(reloader)
+            .oneshot(webhook_request("/git-webhook", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_sync_webhook_returns_head_and_diff() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let cache = RouterCache::new(HashMap::new());
+        let reloader = fixture.reloader(cache.clone(), init_metrics());
+        let app = webhook_app(reloader);
+        let response = app
+            .clone()
+            .oneshot(webhook_request("/git-webhook?wait=true", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("\"head\":\""));
+        assert!(body.contains("\"added\":1"));
+        assert!(body.contains("\"removed\":0"));
+        // The reload is complete by the time the response arrives.
+        assert_eq!(cache.lookup("foo"), Some("https://example.com".to_string()));
+
+        fixture.commit("foo: https://example.org\nbar: https://example.net\n");
+        let response = app
+            .oneshot(webhook_request("/git-webhook", &[(WAIT_HEADER, "1")]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("\"changed\":true"));
+        assert!(body.contains("\"added\":1"));
+        assert!(body.contains("\"modified\":1"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_webhook_returns_422_for_invalid_links() {
+        let fixture = GitFixture::new("foo: \"\"\n");
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), init_metrics());
+        let response = webhook_app(reloader)
+            .oneshot(webhook_request("/git-webhook?wait=1", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("Empty URL for key 'foo'"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_webhook_returns_502_when_git_fails() {
+        let reloader = failing_reloader(RouterCache::new(HashMap::new()), init_metrics());
+        let response = webhook_app(reloader)
+            .oneshot(webhook_request("/git-webhook?wait=true", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_wants_sync_parses_flag_values() {
+        let headers = HeaderMap::new();
+        let params = |wait: Option<&str>| WebhookParams {
+            wait: wait.map(str::to_string),
+        };
+        assert!(wants_sync(&params(Some("")), &headers));
+        assert!(wants_sync(&params(Some("true")), &headers));
+        assert!(!wants_sync(&params(Some("false")), &headers));
+        assert!(!wants_sync(&params(None), &headers));
+        let mut headers = HeaderMap::new();
+        headers.insert(WAIT_HEADER, "yes".parse().unwrap());
+        assert!(wants_sync(&params(None), &headers));
     }
 
     fn loopback_peer() -> IpAddr {
@@ -903,19 +2143,4 @@ mod tests {
         assert!(!clients.contains_key(&stale_ip));
         assert!(clients.contains_key(&fresh_ip));
     }
-
-    #[test]
-    fn test_poll_interval_none_when_unset() {
-        assert_eq!(poll_interval(None), None);
-    }
-
-    #[test]
-    fn test_poll_interval_none_when_zero() {
-        assert_eq!(poll_interval(Some(0)), None);
-    }
-
-    #[test]
-    fn test_poll_interval_some_when_positive() {
-        assert_eq!(poll_interval(Some(60)), Some(Duration::from_secs(60)));
-    }
 }
@@ -0,0 +1,424 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! listen module: opens the sockets the HTTP server serves on. Besides TCP
+//! addresses, a listener can be a Unix domain socket (with the file's mode
+//! and owner set) or a socket inherited through systemd socket activation.
+
+use crate::config::{ListenerConfig, ListenerKind};
+use crate::errors::Error;
+use axum::extract::connect_info::Connected;
+use hyper::server::accept::Accept;
+use std::env;
+use std::fs::{self, Permissions};
+use std::net::{SocketAddr, TcpListener};
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UnixStream;
+
+/// The first file descriptor systemd passes sockets in.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The client address handlers see (`ConnectInfo`) for connections over a
+/// Unix socket, which have none of their own. They count as local clients,
+/// and share one webhook rate limit.
+const UNIX_PEER: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 0);
+
+/// A bound, listening socket.
+pub enum Socket {
+    Tcp(TcpListener),
+    /// A Unix socket and, for one we bound, its path: the socket's own
+    /// address names the private directory it was bound in.
+    Unix(UnixListener, Option<PathBuf>),
+}
+
+impl Socket {
+    /// Bind the socket `config` describes, or take it from `systemd`.
+    pub fn open(config: &ListenerConfig, systemd: &mut SystemdSockets) -> Result<Self, Error> {
+        match config.kind {
+            ListenerKind::Tcp => {
+                let address = config
+                    .address
+                    .as_deref()
+                    .ok_or_else(|| Error::Config("a tcp listener needs an address".to_string()))?;
+                let addr: SocketAddr = address.parse()?;
+                Ok(Socket::Tcp(TcpListener::bind(addr)?))
+            }
+            ListenerKind::Unix => {
+                let (listener, path) = bind_unix(config)?;
+                Ok(Socket::Unix(listener, Some(path)))
+            }
+            ListenerKind::Systemd => systemd.take(config.name.as_deref()),
+        }
+    }
+
+    /// Where the socket listens, for logs.
+    pub fn describe(&self) -> String {
+        let described = match self {
+            Socket::Tcp(tcp) => tcp.local_addr().map(|addr| addr.to_string()),
+            Socket::Unix(_, Some(path)) => Ok(format!("unix:{}", path.display())),
+            Socket::Unix(unix, None) => unix.local_addr().map(|addr| match addr.as_pathname() {
+                Some(path) => format!("unix:{}", path.display()),
+                None => "unix:(unnamed)".to_string(),
+            }),
+        };
+        described.unwrap_or_else(|e| format!("(unknown: {})", e))
+    }
+}
+
+/// Bind a Unix socket at the configured path, replacing a stale socket left
+/// by a previous run (but nothing else), with the configured mode and
+/// ownership. The socket is bound inside a private (0700) directory next to
+/// `path` and only renamed into place once its mode and owner are set, so
+/// it is never reachable with looser permissions; on failure nothing is
+/// left behind.
+fn bind_unix(config: &ListenerConfig) -> Result<(UnixListener, PathBuf), Error> {
+    let path = config
+        .path
+        .as_deref()
+        .ok_or_else(|| Error::Config("a unix listener needs a path".to_string()))?;
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {}
+        Ok(_) => {
+            return Err(Error::Config(format!(
+                "{} exists and is not a socket; not replacing it",
+                path
+            )));
+        }
+        Err(_) => {}
+    }
+    let target = Path::new(path);
+    let name = target
+        .file_name()
+        .ok_or_else(|| Error::Config(format!("{} is not a socket path", path)))?;
+    let staging = target.with_file_name(format!(
+        ".{}.{}-{:x}",
+        name.to_string_lossy(),
+        std::process::id(),
+        fastrand::u64(..)
+    ));
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join(name);
+    let result = bind_staged(&staged, config).and_then(|listener| {
+        fs::rename(&staged, target)?;
+        Ok((listener, target.to_path_buf()))
+    });
+    // Removes the staged socket too if anything failed before the rename.
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// Bind the socket at `staged` and apply the configured mode and owner.
+fn bind_staged(staged: &Path, config: &ListenerConfig) -> Result<UnixListener, Error> {
+    let listener = UnixListener::bind(staged)?;
+    if let Some(mode) = config.mode {
+        fs::set_permissions(staged, Permissions::from_mode(mode))?;
+    }
+    if config.owner.is_some() || config.group.is_some() {
+        let uid = config
+            .owner
+            .as_deref()
+            .map(|owner| resolve_id(owner, "/etc/passwd"))
+            .transpose()?;
+        let gid = config
+            .group
+            .as_deref()
+            .map(|group| resolve_id(group, "/etc/group"))
+            .transpose()?;
+        std::os::unix::fs::chown(staged, uid, gid)?;
+    }
+    Ok(listener)
+}
+
+/// A numeric ID, or the ID of the user or group `name` in `database`
+/// (`/etc/passwd` or `/etc/group`).
+fn resolve_id(name: &str, database: &str) -> Result<u32, Error> {
+    if let Ok(id) = name.parse() {
+        return Ok(id);
+    }
+    let entries = fs::read_to_string(database)?;
+    find_id(&entries, name)
+        .ok_or_else(|| Error::Config(format!("no entry for '{}' in {}", name, database)))
+}
+
+/// The ID (third field) of the `name` entry in passwd/group-style `entries`.
+fn find_id(entries: &str, name: &str) -> Option<u32> {
+    entries.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != name {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+/// Sockets passed in by systemd socket activation, each taken at most once.
+pub struct SystemdSockets {
+    sockets: Vec<(String, Option<OwnedFd>)>,
+}
+
+impl SystemdSockets {
+    /// The sockets passed to this process: `LISTEN_FDS` of them from fd 3,
+    /// named by `LISTEN_FDNAMES`. None unless `LISTEN_PID` is this process.
+    pub fn from_env() -> Self {
+        let var = |key| env::var(key).ok();
+        let passed = passed_fds(
+            var("LISTEN_PID").as_deref(),
+            var("LISTEN_FDS").as_deref(),
+            var("LISTEN_FDNAMES").as_deref(),
+            std::process::id(),
+        );
+        let sockets = passed
+            .into_iter()
+            .map(|(fd, name)| {
+                // SAFETY: systemd passed these descriptors to this process
+                // (LISTEN_PID is ours) and nothing else owns them; this is
+                // the only place they are wrapped.
+                (name, Some(unsafe { OwnedFd::from_raw_fd(fd) }))
+            })
+            .collect();
+        SystemdSockets { sockets }
+    }
+
+    /// The socket named `name`, or the first one passed if `None`.
+    fn take(&mut self, name: Option<&str>) -> Result<Socket, Error> {
+        let slot = match name {
+            Some(name) => self.sockets.iter_mut().find(|(n, _)| n == name),
+            None => self.sockets.first_mut(),
+        };
+        let Some((passed_name, fd)) = slot else {
+            return Err(Error::Config(match name {
+                Some(name) => format!("systemd passed no socket named '{}'", name),
+                None => "systemd passed no sockets".to_string(),
+            }));
+        };
+        let fd = fd.take().ok_or_else(|| {
+            Error::Config(format!(
+                "systemd socket '{}' is used by two listeners",
+                passed_name
+            ))
+        })?;
+        // The inherited descriptor isn't close-on-exec; a duplicate is, so
+        // the git processes spawned for reloads don't hold the socket open.
+        let fd = fd.try_clone()?;
+        let tcp = TcpListener::from(fd);
+        // A Unix socket has no IP address to report.
+        if tcp.local_addr().is_ok() {
+            Ok(Socket::Tcp(tcp))
+        } else {
+            Ok(Socket::Unix(UnixListener::from(OwnedFd::from(tcp)), None))
+        }
+    }
+}
+
+/// The descriptors and names the `LISTEN_*` variables describe, if they are
+/// addressed to `own_pid`. Unnamed sockets are "unknown", as systemd has it.
+fn passed_fds(
+    pid: Option<&str>,
+    fds: Option<&str>,
+    names: Option<&str>,
+    own_pid: u32,
+) -> Vec<(RawFd, String)> {
+    if pid.and_then(|pid| pid.parse::<u32>().ok()) != Some(own_pid) {
+        return Vec::new();
+    }
+    let count = fds.and_then(|fds| fds.parse::<RawFd>().ok()).unwrap_or(0);
+    let mut names = names.unwrap_or_default().split(':');
+    (0..count)
+        .map(|i| {
+            let name = names.next().filter(|name| !name.is_empty());
+            (
+                SD_LISTEN_FDS_START + i,
+                name.unwrap_or("unknown").to_string(),
+            )
+        })
+        .collect()
+}
+
+/// An accepted Unix socket connection.
+pub struct UnixConn {
+    stream: UnixStream,
+}
+
+impl AsyncRead for UnixConn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixConn {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Unix socket clients get `UNIX_PEER` as their address.
+impl Connected<&UnixConn> for SocketAddr {
+    fn connect_info(_: &UnixConn) -> Self {
+        UNIX_PEER
+    }
+}
+
+/// Connections accepted on a Unix socket, for `axum::Server::builder`.
+pub struct UnixIncoming {
+    listener: tokio::net::UnixListener,
+}
+
+impl UnixIncoming {
+    pub fn new(listener: UnixListener) -> Result<Self, Error> {
+        listener.set_nonblocking(true)?;
+        Ok(UnixIncoming {
+            listener: tokio::net::UnixListener::from_std(listener)?,
+        })
+    }
+}
+
+impl Accept for UnixIncoming {
+    type Conn = UnixConn;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.listener
+            .poll_accept(cx)
+            .map(|accepted| Some(accepted.map(|(stream, _)| UnixConn { stream })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    /// A fresh directory under the system temp dir.
+    fn scratch_dir() -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "redirective-listen-{}-{}",
+            std::process::id(),
+            fastrand::u64(..)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn unix_config(path: &std::path::Path) -> ListenerConfig {
+        ListenerConfig {
+            kind: ListenerKind::Unix,
+            path: Some(path.display().to_string()),
+            ..ListenerConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_unix_socket_gets_mode_and_replaces_stale_socket() {
+        let dir = scratch_dir();
+        let path = dir.join("admin.sock");
+        let config = ListenerConfig {
+            mode: Some(0o600),
+            // Our own IDs, so the test needs no privileges.
+            owner: Some(fs::metadata(&dir).unwrap().uid().to_string()),
+            group: Some(fs::metadata(&dir).unwrap().gid().to_string()),
+            ..unix_config(&path)
+        };
+        let first = Socket::open(&config, &mut SystemdSockets { sockets: vec![] }).unwrap();
+        drop(first);
+        // The socket file outlives its listener; binding again replaces it.
+        let socket = Socket::open(&config, &mut SystemdSockets { sockets: vec![] }).unwrap();
+        assert_eq!(socket.describe(), format!("unix:{}", path.display()));
+        let meta = fs::metadata(&path).unwrap();
+        assert!(meta.file_type().is_socket());
+        assert_eq!(meta.mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_unix_socket_does_not_replace_other_files() {
+        let dir = scratch_dir();
+        let path = dir.join("links.yaml");
+        fs::write(&path, "gh: https://github.com\n").unwrap();
+        let opened = Socket::open(&unix_config(&path), &mut SystemdSockets { sockets: vec![] });
+        assert!(matches!(opened, Err(Error::Config(_))));
+        assert!(fs::read_to_string(&path).unwrap().starts_with("gh:"));
+    }
+
+    #[test]
+    fn test_unix_socket_not_left_behind_on_failure() {
+        let dir = scratch_dir();
+        let path = dir.join("admin.sock");
+        let config = ListenerConfig {
+            owner: Some("no-such-user-redirective".to_string()),
+            ..unix_config(&path)
+        };
+        let opened = Socket::open(&config, &mut SystemdSockets { sockets: vec![] });
+        assert!(opened.is_err());
+        // Neither the socket nor its staging directory remains.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_find_id_reads_passwd_style_entries() {
+        let passwd =
+            "root:x:0:0:root:/root:/bin/sh\nredirective:x:998:997::/var/lib:/usr/sbin/nologin\n";
+        assert_eq!(find_id(passwd, "redirective"), Some(998));
+        assert_eq!(find_id(passwd, "root"), Some(0));
+        assert_eq!(find_id(passwd, "redir"), None);
+        assert_eq!(resolve_id("1234", "/nonexistent").unwrap(), 1234);
+    }
+
+    #[test]
+    fn test_passed_fds_only_for_this_process() {
+        assert_eq!(
+            passed_fds(Some("42"), Some("2"), Some("web:admin"), 42),
+            vec![(3, "web".to_string()), (4, "admin".to_string())]
+        );
+        assert_eq!(
+            passed_fds(Some("42"), Some("1"), None, 42),
+            vec![(3, "unknown".to_string())]
+        );
+        assert!(passed_fds(Some("41"), Some("2"), None, 42).is_empty());
+        assert!(passed_fds(None, Some("2"), None, 42).is_empty());
+    }
+
+    #[test]
+    fn test_systemd_sockets_are_taken_by_name_once() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let dir = scratch_dir();
+        let unix = UnixListener::bind(dir.join("admin.sock")).unwrap();
+        let mut systemd = SystemdSockets {
+            sockets: vec![
+                ("web".to_string(), Some(OwnedFd::from(tcp))),
+                ("admin".to_string(), Some(OwnedFd::from(unix))),
+            ],
+        };
+        let admin = systemd.take(Some("admin")).unwrap();
+        assert!(matches!(admin, Socket::Unix(..)));
+        match systemd.take(None).unwrap() {
+            Socket::Tcp(tcp) => assert_eq!(tcp.local_addr().unwrap(), addr),
+            Socket::Unix(..) => panic!("expected the TCP socket"),
+        }
+        assert!(systemd.take(Some("web")).is_err());
+        assert!(systemd.take(Some("metrics")).is_err());
+    }
+}
@@ -3,15 +3,31 @@
 //!
 //! See `.codex/prd.md` and `.codex/architecture.md` for design docs.
 
+mod api;
+mod backoff;
 mod cache;
+mod client;
+mod cluster;
 mod config;
+mod election;
 mod errors;
+mod git;
 mod http;
+mod listen;
 mod metrics;
+mod payload;
+mod relay;
+mod reload;
+mod signature;
+mod source;
+mod tls;
+mod watch;
+mod yaml_edit;
 
 use crate::cache::RouterCache;
-use crate::config::Config;
+use crate::config::{LinkFormat, ReloadMode, ServiceConfig};
 use crate::errors::Error;
+use crate::source::{FileSource, LinkSource};
 
 /// Application entry point.
 #[tokio::main]
@@ -22,25 +38,50 @@ async fn main() -> Result<(), Error> {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
-    // Load configuration (links.yaml and service settings).
-    let config = Config::load("links.yaml")?;
+    // Load service settings (redirective.toml and env overrides).
+    let service = ServiceConfig::load()?;
+    let source = source::from_config(&service);
+    let (links, head) = match source.current().await {
+        Ok(snapshot) => {
+            tracing::info!(source = %source.describe(), "loaded links");
+            (snapshot.links, snapshot.revision)
+        }
+        // Never fall back to the on-disk file past a failed signing check:
+        // an untrusted ref means someone may be tampering with the links.
+        Err(e @ Error::UntrustedCommit { .. }) => return Err(e),
+        // In git mode the on-disk links file is a fallback for running
+        // without a links repository (e.g. local development). In watch and
+        // sqlite modes the configured source is the only source of truth.
+        Err(e) if service.reload.mode == ReloadMode::Git => {
+            let path = &service.git.links_path;
+            tracing::warn!(
+                source = %source.describe(),
+                error = %e,
+                "could not read links from git ref; using {} on disk",
+                path
+            );
+            let format = LinkFormat::resolve(service.reload.format, path);
+            (FileSource::new(path, format).current().await?.links, None)
+        }
+        Err(e) => return Err(e),
+    };
     // Write out shortcodes list for client-side autocomplete
     {
         // Collect and sort shortcode keys
-        let mut codes: Vec<String> = config.links.keys().cloned().collect();
+        let mut codes: Vec<String> = links.keys().cloned().collect();
         codes.sort();
         let content = codes.join("\n");
         if let Err(e) = std::fs::write("static_html/shortcodes.txt", content) {
             tracing::error!("failed to write static_html/shortcodes.txt: {}", e);
         }
     }
-    let cache = RouterCache::new(config.links);
+    let cache = RouterCache::new(links);
 
     // Initialize metrics.
     let metrics = metrics::init_metrics();
 
     // Run the HTTP server.
-    http::run_http_server(cache, metrics, config.service).await?;
+    http::run_http_server(cache, metrics, service, source, head).await?;
 
     Ok(())
 }
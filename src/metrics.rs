@@ -1,7 +1,7 @@
 // (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
 //! metrics module: Prometheus metrics for redirective service.
 
-use prometheus::{HistogramVec, IntCounter, IntCounterVec, Registry};
+use prometheus::{HistogramVec, IntCounter, IntCounterVec, IntGauge, Registry};
 use std::sync::Arc;
 
 /// Prometheus metrics handles.
@@ -15,10 +15,35 @@ pub struct Metrics {
     pub reload_success: IntCounter,
     /// Counter of failed config reloads.
     pub reload_fail: IntCounter,
-    /// Counter of successful webhook relays to the peer node.
-    pub relay_success: IntCounter,
-    /// Counter of failed webhook relays to the peer node.
-    pub relay_fail: IntCounter,
+    /// Counter of reload triggers folded into an already-queued reload.
+    pub reload_coalesced: IntCounter,
+    /// Unix time the most recent reload attempt started.
+    pub reload_last_attempt_timestamp: IntGauge,
+    /// Unix time of the most recent successful reload.
+    pub reload_last_success_timestamp: IntGauge,
+    /// 1 while a reload is running, else 0.
+    pub reload_in_progress: IntGauge,
+    /// Reloads failed in a row since the last success.
+    pub consecutive_reload_failures: IntGauge,
+    /// Number of links currently served.
+    pub links_total: IntGauge,
+    /// Counter of successful webhook relays per peer URL.
+    pub relay_success: IntCounterVec,
+    /// Counter of failed webhook relays per peer URL.
+    pub relay_fail: IntCounterVec,
+    /// Counter of relays given up on after the retry period, per peer URL.
+    pub relay_dropped: IntCounterVec,
+    /// Number of peers with a relay waiting in the outbox.
+    pub relay_outbox_depth: IntGauge,
+    /// Seconds this node and a peer have been serving different link
+    /// tables; 0 while they agree.
+    pub cluster_divergence_seconds: IntGauge,
+    /// 1 once the divergence has lasted past the configured threshold.
+    pub cluster_diverged: IntGauge,
+    /// 1 while this node is the elected leader (0 without leader election).
+    pub cluster_leader: IntGauge,
+    /// Counter of webhook requests rejected for a missing or bad signature.
+    pub webhook_unauthorized: IntCounter,
     /// The registry holding all metrics.
     pub registry: Arc<Registry>,
 }
@@ -60,28 +85,145 @@ pub fn init_metrics() -> Metrics {
     registry
         .register(Box::new(reload_fail.clone()))
         .expect("failed to register reload_fail");
-    // Counter of successful peer relays
-    let relay_success = IntCounter::new(
-        "relay_success",
-        "Counter of successful webhook relays to peer",
+    // Counter of reload triggers coalesced into a pending run
+    let reload_coalesced = IntCounter::new(
+        "reload_coalesced",
+        "Counter of reload triggers folded into an already-queued reload",
+    )
+    .expect("failed to create reload_coalesced metric");
+    registry
+        .register(Box::new(reload_coalesced.clone()))
+        .expect("failed to register reload_coalesced");
+    // Reload status gauges (mirrors /reload/status)
+    let reload_last_attempt_timestamp = IntGauge::new(
+        "reload_last_attempt_timestamp_seconds",
+        "Unix time the most recent reload attempt started",
+    )
+    .expect("failed to create reload_last_attempt_timestamp_seconds metric");
+    registry
+        .register(Box::new(reload_last_attempt_timestamp.clone()))
+        .expect("failed to register reload_last_attempt_timestamp_seconds");
+    let reload_last_success_timestamp = IntGauge::new(
+        "reload_last_success_timestamp_seconds",
+        "Unix time of the most recent successful reload",
+    )
+    .expect("failed to create reload_last_success_timestamp_seconds metric");
+    registry
+        .register(Box::new(reload_last_success_timestamp.clone()))
+        .expect("failed to register reload_last_success_timestamp_seconds");
+    let reload_in_progress =
+        IntGauge::new("reload_in_progress", "1 while a reload is running, else 0")
+            .expect("failed to create reload_in_progress metric");
+    registry
+        .register(Box::new(reload_in_progress.clone()))
+        .expect("failed to register reload_in_progress");
+    let consecutive_reload_failures = IntGauge::new(
+        "consecutive_reload_failures",
+        "Reloads failed in a row since the last success",
+    )
+    .expect("failed to create consecutive_reload_failures metric");
+    registry
+        .register(Box::new(consecutive_reload_failures.clone()))
+        .expect("failed to register consecutive_reload_failures");
+    let links_total = IntGauge::new("links_total", "Number of links currently served")
+        .expect("failed to create links_total metric");
+    registry
+        .register(Box::new(links_total.clone()))
+        .expect("failed to register links_total");
+    // Counter of successful relays per peer label
+    let relay_success = IntCounterVec::new(
+        prometheus::Opts::new(
+            "relay_success",
+            "Counter of successful webhook relays per peer",
+        ),
+        &["peer"],
     )
     .expect("failed to create relay_success metric");
     registry
         .register(Box::new(relay_success.clone()))
         .expect("failed to register relay_success");
-    // Counter of failed peer relays
-    let relay_fail = IntCounter::new("relay_fail", "Counter of failed webhook relays to peer")
-        .expect("failed to create relay_fail metric");
+    // Counter of failed relays per peer label
+    let relay_fail = IntCounterVec::new(
+        prometheus::Opts::new("relay_fail", "Counter of failed webhook relays per peer"),
+        &["peer"],
+    )
+    .expect("failed to create relay_fail metric");
     registry
         .register(Box::new(relay_fail.clone()))
         .expect("failed to register relay_fail");
+    // Counter of relays given up on, per peer label
+    let relay_dropped = IntCounterVec::new(
+        prometheus::Opts::new(
+            "relay_dropped",
+            "Counter of webhook relays given up on after the retry period, per peer",
+        ),
+        &["peer"],
+    )
+    .expect("failed to create relay_dropped metric");
+    registry
+        .register(Box::new(relay_dropped.clone()))
+        .expect("failed to register relay_dropped");
+    let relay_outbox_depth = IntGauge::new(
+        "relay_outbox_depth",
+        "Number of peers with a webhook relay waiting in the outbox",
+    )
+    .expect("failed to create relay_outbox_depth metric");
+    registry
+        .register(Box::new(relay_outbox_depth.clone()))
+        .expect("failed to register relay_outbox_depth");
+    // Cluster consistency gauges (mirrors /cluster/status)
+    let cluster_divergence_seconds = IntGauge::new(
+        "cluster_divergence_seconds",
+        "Seconds this node and a peer have been serving different link tables",
+    )
+    .expect("failed to create cluster_divergence_seconds metric");
+    registry
+        .register(Box::new(cluster_divergence_seconds.clone()))
+        .expect("failed to register cluster_divergence_seconds");
+    let cluster_diverged = IntGauge::new(
+        "cluster_diverged",
+        "1 once nodes have served different link tables for longer than the threshold",
+    )
+    .expect("failed to create cluster_diverged metric");
+    registry
+        .register(Box::new(cluster_diverged.clone()))
+        .expect("failed to register cluster_diverged");
+    let cluster_leader = IntGauge::new(
+        "cluster_leader",
+        "1 while this node is the elected leader that polls for the cluster",
+    )
+    .expect("failed to create cluster_leader metric");
+    registry
+        .register(Box::new(cluster_leader.clone()))
+        .expect("failed to register cluster_leader");
+    // Counter of webhook requests failing signature verification
+    let webhook_unauthorized = IntCounter::new(
+        "webhook_unauthorized",
+        "Counter of webhook requests rejected for a missing or invalid signature",
+    )
+    .expect("failed to create webhook_unauthorized metric");
+    registry
+        .register(Box::new(webhook_unauthorized.clone()))
+        .expect("failed to register webhook_unauthorized");
     Metrics {
         redirect_total,
         redirect_latency,
         reload_success,
         reload_fail,
+        reload_coalesced,
+        reload_last_attempt_timestamp,
+        reload_last_success_timestamp,
+        reload_in_progress,
+        consecutive_reload_failures,
+        links_total,
         relay_success,
         relay_fail,
+        relay_dropped,
+        relay_outbox_depth,
+        cluster_divergence_seconds,
+        cluster_diverged,
+        cluster_leader,
+        webhook_unauthorized,
         registry: Arc::new(registry),
     }
 }
@@ -101,16 +243,32 @@ mod tests {
             .observe(0.0);
         metrics.reload_success.inc();
         metrics.reload_fail.inc();
-        metrics.relay_success.inc();
-        metrics.relay_fail.inc();
+        metrics.reload_coalesced.inc();
+        metrics.relay_success.with_label_values(&["p"]).inc();
+        metrics.relay_fail.with_label_values(&["p"]).inc();
+        metrics.relay_dropped.with_label_values(&["p"]).inc();
+        metrics.webhook_unauthorized.inc();
+        metrics.links_total.set(3);
         let families = metrics.registry.gather();
         let names: Vec<_> = families.iter().map(|f| f.name()).collect();
         // Ensure counters are registered
         assert!(names.contains(&"redirect_total"));
         assert!(names.contains(&"reload_success"));
         assert!(names.contains(&"reload_fail"));
+        assert!(names.contains(&"reload_coalesced"));
         assert!(names.contains(&"relay_success"));
         assert!(names.contains(&"relay_fail"));
+        assert!(names.contains(&"relay_dropped"));
+        assert!(names.contains(&"relay_outbox_depth"));
+        assert!(names.contains(&"cluster_divergence_seconds"));
+        assert!(names.contains(&"cluster_diverged"));
+        assert!(names.contains(&"cluster_leader"));
+        assert!(names.contains(&"webhook_unauthorized"));
+        assert!(names.contains(&"reload_last_attempt_timestamp_seconds"));
+        assert!(names.contains(&"reload_last_success_timestamp_seconds"));
+        assert!(names.contains(&"reload_in_progress"));
+        assert!(names.contains(&"links_total"));
+        assert!(names.contains(&"consecutive_reload_failures"));
         // Histogram produces bucket, sum, and count families
         assert!(
             names
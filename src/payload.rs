@@ -0,0 +1,254 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! payload module: interprets GitHub, Gitea and GitLab push-webhook payloads
+//! so pushes that cannot change the served table don't trigger a reload.
+
+use crate::config::GitConfig;
+use axum::http::HeaderMap;
+use serde::Deserialize;
+
+/// GitHub event name header (Gitea sends it too, for compatibility).
+const GITHUB_EVENT_HEADER: &str = "x-github-event";
+/// Gitea event name header.
+const GITEA_EVENT_HEADER: &str = "x-gitea-event";
+/// GitLab event name header.
+const GITLAB_EVENT_HEADER: &str = "x-gitlab-event";
+
+/// GitHub and GitLab both cap the commit list in a push payload at 20; past
+/// that, the file lists can't prove a links file was left alone.
+const MAX_LISTED_COMMITS: usize = 20;
+
+/// What the webhook should do with a request.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// Reload the link table.
+    Reload,
+    /// A forge checking the endpoint is reachable (GitHub/Gitea `ping`).
+    Ping,
+    /// A well-formed event that cannot affect the served table.
+    Ignore(String),
+}
+
+/// Which pushes are allowed to trigger a reload.
+#[derive(Clone, Debug, Default)]
+pub struct PushFilter {
+    /// Only pushes to this branch reload; `None` accepts any ref.
+    pub branch: Option<String>,
+    /// Only pushes touching one of these paths reload (an entry ending in
+    /// `/` matches everything under that directory); empty accepts any push.
+    pub paths: Vec<String>,
+}
+
+/// The parts of a push payload we look at. GitHub, Gitea and GitLab all use
+/// these field names.
+#[derive(Deserialize)]
+struct PushPayload {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    #[serde(default)]
+    commits: Vec<PushCommit>,
+    /// GitLab only: commits in the push, which may exceed `commits.len()`.
+    total_commits_count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct PushCommit {
+    #[serde(default)]
+    added: Vec<String>,
+    #[serde(default)]
+    modified: Vec<String>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+/// The branch reloads read from, derived from the configured git ref:
+/// `origin/main`, `refs/remotes/origin/main` and `refs/heads/main` all give
+/// `main`. Returns `None` for refs that aren't branches (tags, commit hashes),
+/// which disables the branch filter.
+pub fn tracked_branch(git: &GitConfig) -> Option<String> {
+    let git_ref = git.git_ref.as_str();
+    let remote_prefix = format!("{}/", git.remote);
+    git_ref
+        .strip_prefix("refs/remotes/")
+        .and_then(|r| r.strip_prefix(&remote_prefix))
+        .or_else(|| git_ref.strip_prefix(&remote_prefix))
+        .or_else(|| git_ref.strip_prefix("refs/heads/"))
+        .filter(|branch| !branch.is_empty())
+        .map(str::to_string)
+}
+
+fn touches(paths: &[String], file: &str) -> bool {
+    paths.iter().any(|p| {
+        if p.ends_with('/') {
+            file.starts_with(p.as_str())
+        } else {
+            file == p
+        }
+    })
+}
+
+/// Classify a webhook request.
+///
+/// Requests that don't identify as a forge event and don't parse as a push
+/// payload (e.g. a bare `curl -X POST`, or a relay from a peer node) always
+/// reload, as do pushes whose file lists may have been truncated.
+pub fn classify(headers: &HeaderMap, body: &[u8], filter: &PushFilter) -> Trigger {
+    let event = [GITHUB_EVENT_HEADER, GITEA_EVENT_HEADER, GITLAB_EVENT_HEADER]
+        .iter()
+        .find_map(|name| headers.get(*name).and_then(|v| v.to_str().ok()));
+    match event {
+        Some("ping") => return Trigger::Ping,
+        Some("push") | Some("Push Hook") | None => {}
+        Some(other) => return Trigger::Ignore(format!("event '{}' is not a push", other)),
+    }
+    let Ok(push) = serde_json::from_slice::<PushPayload>(body) else {
+        return Trigger::Reload;
+    };
+    if let Some(branch) = &filter.branch {
+        let pushed = push.git_ref.strip_prefix("refs/heads/");
+        if pushed != Some(branch.as_str()) {
+            return Trigger::Ignore(format!(
+                "push to {} is not for branch {}",
+                push.git_ref, branch
+            ));
+        }
+    }
+    if filter.paths.is_empty() {
+        return Trigger::Reload;
+    }
+    let listed = push.commits.len();
+    let truncated = listed >= MAX_LISTED_COMMITS
+        || push.total_commits_count.is_some_and(|total| total > listed);
+    let touched = push.commits.iter().any(|c| {
+        c.added
+            .iter()
+            .chain(&c.modified)
+            .chain(&c.removed)
+            .any(|f| touches(&filter.paths, f))
+    });
+    if touched || truncated || listed == 0 {
+        Trigger::Reload
+    } else {
+        Trigger::Ignore("push does not touch any links file".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    fn filter(branch: Option<&str>, paths: &[&str]) -> PushFilter {
+        PushFilter {
+            branch: branch.map(str::to_string),
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    fn git(remote: &str, git_ref: &str) -> GitConfig {
+        GitConfig {
+            remote: remote.to_string(),
+            git_ref: git_ref.to_string(),
+            ..GitConfig::default()
+        }
+    }
+
+    const PUSH_MAIN: &str = r#"{
+        "ref": "refs/heads/main",
+        "commits": [{"added": [], "modified": ["links.yaml"], "removed": []}]
+    }"#;
+    const PUSH_README: &str = r#"{
+        "ref": "refs/heads/main",
+        "commits": [{"added": ["docs/x.md"], "modified": ["README.md"], "removed": []}]
+    }"#;
+
+    #[test]
+    fn test_tracked_branch_from_git_ref() {
+        assert_eq!(
+            tracked_branch(&git("origin", "origin/main")),
+            Some("main".to_string())
+        );
+        assert_eq!(
+            tracked_branch(&git("upstream", "refs/remotes/upstream/release/v2")),
+            Some("release/v2".to_string())
+        );
+        assert_eq!(
+            tracked_branch(&git("origin", "refs/heads/prod")),
+            Some("prod".to_string())
+        );
+        assert_eq!(tracked_branch(&git("origin", "v1.2.0")), None);
+    }
+
+    #[test]
+    fn test_ping_events_answered() {
+        let h = headers(GITHUB_EVENT_HEADER, "ping");
+        assert_eq!(classify(&h, b"{}", &PushFilter::default()), Trigger::Ping);
+    }
+
+    #[test]
+    fn test_non_push_events_ignored() {
+        let h = headers(GITLAB_EVENT_HEADER, "Merge Request Hook");
+        assert!(matches!(
+            classify(&h, b"{}", &PushFilter::default()),
+            Trigger::Ignore(_)
+        ));
+    }
+
+    #[test]
+    fn test_branch_filter() {
+        let f = filter(Some("main"), &[]);
+        let h = headers(GITHUB_EVENT_HEADER, "push");
+        assert_eq!(classify(&h, PUSH_MAIN.as_bytes(), &f), Trigger::Reload);
+        let other = PUSH_MAIN.replace("refs/heads/main", "refs/heads/feature");
+        assert!(matches!(
+            classify(&h, other.as_bytes(), &f),
+            Trigger::Ignore(_)
+        ));
+        let tag = PUSH_MAIN.replace("refs/heads/main", "refs/tags/main");
+        assert!(matches!(
+            classify(&h, tag.as_bytes(), &f),
+            Trigger::Ignore(_)
+        ));
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let f = filter(Some("main"), &["links.yaml", "links.d/"]);
+        let h = headers(GITLAB_EVENT_HEADER, "Push Hook");
+        assert_eq!(classify(&h, PUSH_MAIN.as_bytes(), &f), Trigger::Reload);
+        assert!(matches!(
+            classify(&h, PUSH_README.as_bytes(), &f),
+            Trigger::Ignore(_)
+        ));
+        let in_dir = PUSH_README.replace("docs/x.md", "links.d/team.yaml");
+        assert_eq!(classify(&h, in_dir.as_bytes(), &f), Trigger::Reload);
+    }
+
+    #[test]
+    fn test_path_filter_reloads_when_commit_list_truncated() {
+        let f = filter(None, &["links.yaml"]);
+        let truncated = PUSH_README.replacen('{', r#"{"total_commits_count": 30,"#, 1);
+        assert_eq!(
+            classify(&HeaderMap::new(), truncated.as_bytes(), &f),
+            Trigger::Reload
+        );
+    }
+
+    #[test]
+    fn test_unknown_payloads_reload() {
+        let f = filter(Some("main"), &["links.yaml"]);
+        assert_eq!(classify(&HeaderMap::new(), b"", &f), Trigger::Reload);
+        assert_eq!(
+            classify(&HeaderMap::new(), b"not json", &f),
+            Trigger::Reload
+        );
+        assert_eq!(
+            classify(&HeaderMap::new(), br#"{"hello": "world"}"#, &f),
+            Trigger::Reload
+        );
+    }
+}
@@ -0,0 +1,602 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! relay module: relays reload webhooks (or, in snapshot sync mode, the
+//! reloaded table itself) to peer nodes through an outbox that retries failed
+//! relays with backoff, so a peer that is restarting still catches up once it
+//! is back instead of waiting for its own poll.
+
+use crate::backoff::Backoff;
+use crate::client::https_client;
+use crate::cluster::{self, TableSnapshot};
+use crate::metrics::Metrics;
+use crate::reload::{Reloader, unix_now};
+use crate::signature;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+/// Header marking a webhook request as relayed from a peer node.
+/// Requests carrying this header are never relayed again (loop prevention).
+/// With a cluster secret configured, it is only honored alongside a valid
+/// `X-Redirective-Relay-Signature`.
+pub const RELAY_HEADER: &str = "x-redirective-relay";
+
+/// Timeout for a single relay attempt.
+const RELAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delay before the first retry of a failed relay; doubles per failure.
+const RETRY_BASE: Duration = Duration::from_secs(1);
+
+/// Longest delay between retries of a failed relay.
+const RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// Secrets outgoing relays are signed with.
+#[derive(Clone, Default)]
+pub struct RelaySecrets {
+    /// Webhook secret: signs the body the way GitHub would, so a peer
+    /// sharing it accepts the relay as a webhook.
+    pub webhook: Option<String>,
+    /// Cluster secret: a timestamped relay signature, so a peer sharing it
+    /// trusts the request as a relay.
+    pub cluster: Option<String>,
+}
+
+/// What the outbox sends a peer.
+#[derive(Clone)]
+pub enum Delivery {
+    /// An empty webhook POST to the peer URL; the peer reloads from its own
+    /// source.
+    Webhook,
+    /// The table `Reloader` is serving at send time, POSTed to the peer's
+    /// `/cluster/snapshot`. Needs the cluster secret.
+    Snapshot(Box<Reloader>),
+}
+
+fn relay_signature(secret: &str, body: &[u8]) -> String {
+    let nonce = format!("{:016x}", fastrand::u64(..));
+    signature::sign_relay(secret, unix_now(), &nonce, body)
+}
+
+/// Build the request relaying `delivery` to `peer_url`: marked with the
+/// relay-guard header and signed with whichever of `secrets` are configured.
+fn relay_request(
+    peer_url: &str,
+    secrets: &RelaySecrets,
+    delivery: &Delivery,
+) -> Result<hyper::Request<hyper::Body>, String> {
+    match delivery {
+        Delivery::Webhook => {
+            let mut request = hyper::Request::builder()
+                .method(hyper::Method::POST)
+                .uri(peer_url)
+                .header(RELAY_HEADER, "1");
+            if let Some(secret) = secrets.webhook.as_deref() {
+                request = request.header(
+                    signature::HUB_SIGNATURE_HEADER,
+                    signature::sign(secret, b""),
+                );
+            }
+            if let Some(secret) = secrets.cluster.as_deref() {
+                request = request.header(
+                    signature::RELAY_SIGNATURE_HEADER,
+                    relay_signature(secret, b""),
+                );
+            }
+            request
+                .body(hyper::Body::empty())
+                .map_err(|e| e.to_string())
+        }
+        Delivery::Snapshot(reloader) => {
+            let secret = secrets
+                .cluster
+                .as_deref()
+                .ok_or("snapshot sync needs a cluster secret")?;
+            let url = cluster::peer_route(peer_url, cluster::SNAPSHOT_ROUTE)
+                .ok_or("peer URL has no host")?;
+            let body =
+                serde_json::to_vec(&TableSnapshot::current(reloader)).map_err(|e| e.to_string())?;
+            hyper::Request::builder()
+                .method(hyper::Method::POST)
+                .uri(url)
+                .header(RELAY_HEADER, "1")
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .header(
+                    signature::RELAY_SIGNATURE_HEADER,
+                    relay_signature(secret, &body),
+                )
+                .body(hyper::Body::from(body))
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Send one relay of `delivery` to the peer. Returns whether the peer
+/// accepted it; failures are logged and counted.
+async fn relay_to_peer(
+    peer_url: &str,
+    secrets: &RelaySecrets,
+    delivery: &Delivery,
+    metrics: &Metrics,
+) -> bool {
+    let request = match relay_request(peer_url, secrets, delivery) {
+        Ok(req) => req,
+        Err(e) => {
+            tracing::error!(peer = peer_url, error = %e, "invalid peer_url; relay skipped");
+            metrics.relay_fail.with_label_values(&[peer_url]).inc();
+            return false;
+        }
+    };
+    match tokio::time::timeout(RELAY_TIMEOUT, https_client().request(request)).await {
+        Ok(Ok(resp)) if resp.status().is_success() => {
+            tracing::info!(peer = peer_url, status = %resp.status(), "relayed to peer");
+            metrics.relay_success.with_label_values(&[peer_url]).inc();
+            return true;
+        }
+        Ok(Ok(resp)) => {
+            tracing::warn!(peer = peer_url, status = %resp.status(), "peer relay rejected");
+        }
+        Ok(Err(e)) => {
+            tracing::warn!(peer = peer_url, error = %e, "peer relay failed");
+        }
+        Err(_) => {
+            tracing::warn!(
+                peer = peer_url,
+                timeout_secs = RELAY_TIMEOUT.as_secs(),
+                "peer relay timed out"
+            );
+        }
+    }
+    metrics.relay_fail.with_label_values(&[peer_url]).inc();
+    false
+}
+
+/// A relay waiting to be delivered to one peer.
+struct Pending {
+    /// Unix time the relay was first enqueued; retries stop `retry_for`
+    /// after it.
+    since: u64,
+    /// Set when another relay to the same peer was enqueued while this one
+    /// was pending; once delivered, it is sent once more, so the peer
+    /// never misses a reload that happened after an attempt went out.
+    again: bool,
+}
+
+/// What `RelayOutbox::deliver` does after an attempt.
+#[derive(Debug, PartialEq)]
+enum Attempt {
+    /// Delivered, but another relay was merged in meanwhile: send again.
+    Again,
+    /// Delivered or given up on; the entry is gone.
+    Done,
+    /// Failed within the retry period: back off and retry.
+    Retry,
+}
+
+/// On-disk form of a pending relay.
+#[derive(Serialize, Deserialize)]
+struct PersistedRelay {
+    peer: String,
+    since: u64,
+}
+
+struct OutboxInner {
+    /// At most one pending relay per peer.
+    pending: Mutex<HashMap<String, Pending>>,
+    secrets: RelaySecrets,
+    delivery: Delivery,
+    metrics: Metrics,
+    backoff: Backoff,
+    retry_for: Duration,
+    /// File the pending relays are mirrored to, if any.
+    path: Option<PathBuf>,
+}
+
+/// Outbox of relays to peers. Each peer has at most one pending relay, which
+/// is retried with jittered backoff until the peer accepts it or `retry_for`
+/// has passed since it was enqueued. With a `path`, pending relays are
+/// written to disk and resumed at startup, so a restart doesn't lose them.
+#[derive(Clone)]
+pub struct RelayOutbox {
+    inner: Arc<OutboxInner>,
+}
+
+impl RelayOutbox {
+    /// Create the outbox, resuming any relays persisted at `path` that are
+    /// still within `retry_for`. Must be called within a tokio runtime if
+    /// there are relays to resume.
+    pub fn new(
+        secrets: RelaySecrets,
+        delivery: Delivery,
+        metrics: Metrics,
+        retry_for: Duration,
+        path: Option<PathBuf>,
+    ) -> Self {
+        let resumed: Vec<PersistedRelay> = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(relays) => Some(relays),
+                Err(e) => {
+                    tracing::warn!(error = %e, "ignoring unreadable relay outbox file");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let now = unix_now();
+        let pending: HashMap<String, Pending> = resumed
+            .into_iter()
+            .filter(|relay| now.saturating_sub(relay.since) < retry_for.as_secs())
+            .map(|relay| {
+                let pending = Pending {
+                    since: relay.since,
+                    again: false,
+                };
+                (relay.peer, pending)
+            })
+            .collect();
+        let peers: Vec<String> = pending.keys().cloned().collect();
+        let outbox = RelayOutbox {
+            inner: Arc::new(OutboxInner {
+                pending: Mutex::new(pending),
+                secrets,
+                delivery,
+                metrics,
+                backoff: Backoff::new(RETRY_BASE, RETRY_MAX),
+                retry_for,
+                path,
+            }),
+        };
+        {
+            let pending = outbox.inner.pending.lock().unwrap();
+            outbox.sync(&pending);
+        }
+        for peer in peers {
+            tracing::info!(peer = %peer, "resuming pending relay");
+            task::spawn(outbox.clone().deliver(peer));
+        }
+        outbox
+    }
+
+    /// Queue a relay to `peer_url`. If one is already pending for that peer,
+    /// the two are merged: the peer gets one more relay after the pending
+    /// one is delivered, not one per call.
+    pub fn enqueue(&self, peer_url: String) {
+        let mut pending = self.inner.pending.lock().unwrap();
+        if let Some(existing) = pending.get_mut(&peer_url) {
+            existing.again = true;
+            tracing::debug!(peer = %peer_url, "relay already pending; merged");
+            return;
+        }
+        pending.insert(
+            peer_url.clone(),
+            Pending {
+                since: unix_now(),
+                again: false,
+            },
+        );
+        self.sync(&pending);
+        drop(pending);
+        task::spawn(self.clone().deliver(peer_url));
+    }
+
+    /// Deliver the pending relay to `peer_url`, retrying until it is
+    /// accepted or given up on, then remove it from the outbox.
+    async fn deliver(self, peer_url: String) {
+        let mut failures = 0;
+        loop {
+            let delivered = relay_to_peer(
+                &peer_url,
+                &self.inner.secrets,
+                &self.inner.delivery,
+                &self.inner.metrics,
+            )
+            .await;
+            match self.settle(&peer_url, delivered) {
+                Attempt::Again => failures = 0,
+                Attempt::Done => return,
+                Attempt::Retry => {
+                    tokio::time::sleep(self.inner.backoff.jittered(failures)).await;
+                    failures = failures.saturating_add(1);
+                }
+            }
+        }
+    }
+
+    /// Decide what follows an attempt to relay to `peer_url`. Removing the
+    /// entry happens under the same lock as the check for a merged relay,
+    /// so an `enqueue` either lands before it (and is sent) or finds no
+    /// entry and starts a new delivery.
+    fn settle(&self, peer_url: &str, delivered: bool) -> Attempt {
+        let mut pending = self.inner.pending.lock().unwrap();
+        let Some(entry) = pending.get_mut(peer_url) else {
+            return Attempt::Done;
+        };
+        if delivered && entry.again {
+            entry.again = false;
+            entry.since = unix_now();
+            return Attempt::Again;
+        }
+        if !delivered {
+            let waited = Duration::from_secs(unix_now().saturating_sub(entry.since));
+            if waited < self.inner.retry_for {
+                return Attempt::Retry;
+            }
+            tracing::error!(
+                peer = %peer_url,
+                retry_secs = self.inner.retry_for.as_secs(),
+                "giving up on peer relay"
+            );
+            self.inner
+                .metrics
+                .relay_dropped
+                .with_label_values(&[peer_url])
+                .inc();
+        }
+        pending.remove(peer_url);
+        self.sync(&pending);
+        Attempt::Done
+    }
+
+    /// Publish the queue depth and, if configured, mirror the pending
+    /// relays to disk. Called with the pending lock held, so writes happen
+    /// in order.
+    fn sync(&self, pending: &HashMap<String, Pending>) {
+        self.inner
+            .metrics
+            .relay_outbox_depth
+            .set(pending.len() as i64);
+        let Some(path) = &self.inner.path else {
+            return;
+        };
+        let relays: Vec<PersistedRelay> = pending
+            .iter()
+            .map(|(peer, entry)| PersistedRelay {
+                peer: peer.clone(),
+                since: entry.since,
+            })
+            .collect();
+        let json = serde_json::to_string(&relays).expect("relays serialize");
+        // Write then rename, so a crash mid-write never leaves a torn file.
+        let tmp = path.with_extension("tmp");
+        if let Err(e) = std::fs::write(&tmp, json).and_then(|_| std::fs::rename(&tmp, path)) {
+            tracing::warn!(path = %path.display(), error = %e, "failed to persist relay outbox");
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+
+    impl RelayOutbox {
+        /// Number of peers with a relay pending.
+        pub fn depth(&self) -> usize {
+            self.inner.pending.lock().unwrap().len()
+        }
+
+        /// Wait (up to 10 seconds) for every pending relay to be delivered
+        /// or given up on.
+        pub async fn drained(&self) {
+            for _ in 0..1000 {
+                if self.depth() == 0 {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("relay outbox still has {} pending", self.depth());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::RouterCache;
+    use crate::cluster::{ClusterMonitor, ReplayGuard, SnapshotReceiver};
+    use crate::config::{GitConfig, LinkFormat};
+    use crate::metrics::init_metrics;
+    use crate::reload::{ReloadCoordinator, ReloadStatus};
+    use crate::source::GitSource;
+    use axum::{Router, http::StatusCode, routing::post};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Mutex as TokioMutex;
+
+    /// A peer that answers 503 to its first `failures` relays, then 202.
+    /// Returns its URL and the number of relays it received.
+    async fn spawn_flaky_peer(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let received = Arc::new(AtomicUsize::new(0));
+        let count = received.clone();
+        let app = Router::new().route(
+            "/git-webhook",
+            post(move || {
+                let seen = count.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if seen < failures {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        StatusCode::ACCEPTED
+                    }
+                }
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        (format!("http://{}/git-webhook", addr), received)
+    }
+
+    fn outbox(metrics: &Metrics, retry_secs: u64, path: Option<PathBuf>) -> RelayOutbox {
+        RelayOutbox::new(
+            RelaySecrets::default(),
+            Delivery::Webhook,
+            metrics.clone(),
+            Duration::from_secs(retry_secs),
+            path,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_failed_relay_is_retried_until_accepted() {
+        let (peer, received) = spawn_flaky_peer(1).await;
+        let metrics = init_metrics();
+        let outbox = outbox(&metrics, 60, None);
+        outbox.enqueue(peer.clone());
+        assert_eq!(metrics.relay_outbox_depth.get(), 1);
+        outbox.drained().await;
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+        assert_eq!(metrics.relay_fail.with_label_values(&[&peer]).get(), 1);
+        assert_eq!(metrics.relay_success.with_label_values(&[&peer]).get(), 1);
+        assert_eq!(metrics.relay_outbox_depth.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_relay_dropped_after_retry_period() {
+        let (peer, received) = spawn_flaky_peer(usize::MAX).await;
+        let metrics = init_metrics();
+        // A zero retry period gives up after the first failed attempt.
+        let outbox = outbox(&metrics, 0, None);
+        outbox.enqueue(peer.clone());
+        outbox.drained().await;
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+        assert_eq!(metrics.relay_dropped.with_label_values(&[&peer]).get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pending_relays_to_a_peer_are_merged() {
+        let (peer, received) = spawn_flaky_peer(1).await;
+        let metrics = init_metrics();
+        let outbox = outbox(&metrics, 60, None);
+        for _ in 0..5 {
+            outbox.enqueue(peer.clone());
+        }
+        assert_eq!(outbox.depth(), 1);
+        outbox.drained().await;
+        // One failed attempt, its retry, and one more for the merged relays.
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+        assert_eq!(metrics.relay_success.with_label_values(&[&peer]).get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_while_delivery_finishes_is_not_lost() {
+        let (peer, received) = spawn_flaky_peer(0).await;
+        let metrics = init_metrics();
+        let outbox = outbox(&metrics, 60, None);
+        let pending = |again| Pending {
+            since: unix_now(),
+            again,
+        };
+        // Enqueued just before the delivery settles: merged, then sent again.
+        outbox
+            .inner
+            .pending
+            .lock()
+            .unwrap()
+            .insert(peer.clone(), pending(false));
+        outbox.enqueue(peer.clone());
+        assert_eq!(outbox.settle(&peer, true), Attempt::Again);
+        assert_eq!(outbox.settle(&peer, true), Attempt::Done);
+        assert_eq!(outbox.depth(), 0);
+        // Enqueued just after: no entry left to merge into, so it starts a
+        // delivery of its own.
+        outbox.enqueue(peer.clone());
+        outbox.drained().await;
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pending_relays_survive_restart() {
+        let dir = std::env::temp_dir().join(format!(
+            "redirective-outbox-{}-{}",
+            std::process::id(),
+            fastrand::u64(..)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("outbox.json");
+        let (peer, received) = spawn_flaky_peer(0).await;
+        let stale = "http://127.0.0.1:9/git-webhook";
+        let persisted = vec![
+            PersistedRelay {
+                peer: peer.clone(),
+                since: unix_now(),
+            },
+            PersistedRelay {
+                peer: stale.to_string(),
+                since: unix_now() - 120,
+            },
+        ];
+        std::fs::write(&path, serde_json::to_string(&persisted).unwrap()).unwrap();
+        let metrics = init_metrics();
+        let outbox = outbox(&metrics, 60, Some(path.clone()));
+        // The relay past its retry period is not resumed.
+        assert_eq!(outbox.depth(), 1);
+        outbox.drained().await;
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A reloader serving `table` from a never-reloaded source.
+    fn reloader_serving(table: &[(&str, &str)], metrics: &Metrics) -> Reloader {
+        let table: std::collections::HashMap<_, _> = table
+            .iter()
+            .map(|(code, url)| (code.to_string(), url.to_string()))
+            .collect();
+        let status = ReloadStatus::new(metrics.clone(), table.len(), Some("abc123".into()));
+        let source = GitSource::new(GitConfig::default(), LinkFormat::Yaml);
+        Reloader::new(
+            RouterCache::new(table),
+            metrics.clone(),
+            status,
+            Arc::new(source),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_delivery_installs_table_on_peer() {
+        let secrets = RelaySecrets {
+            webhook: None,
+            cluster: Some("cluster secret".to_string()),
+        };
+        let peer = reloader_serving(&[], &init_metrics());
+        let receiver = SnapshotReceiver::new(
+            ReloadCoordinator::new(peer.clone(), Arc::new(TokioMutex::new(()))),
+            secrets.cluster.clone().unwrap(),
+            Arc::new(ReplayGuard::new(300)),
+        );
+        let monitor = ClusterMonitor::new(peer.clone(), None, &[], Duration::ZERO);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(cluster::routes(monitor, Some(receiver)).into_make_service()),
+        );
+
+        let metrics = init_metrics();
+        let local = reloader_serving(&[("a", "https://a.example")], &metrics);
+        let outbox = RelayOutbox::new(
+            secrets,
+            Delivery::Snapshot(Box::new(local.clone())),
+            metrics.clone(),
+            Duration::from_secs(60),
+            None,
+        );
+        let peer_url = format!("http://{}/git-webhook", addr);
+        outbox.enqueue(peer_url.clone());
+        outbox.drained().await;
+        assert_eq!(
+            metrics.relay_success.with_label_values(&[&peer_url]).get(),
+            1
+        );
+        assert_eq!(peer.cache.snapshot(), local.cache.snapshot());
+        assert_eq!(peer.status.report().head.as_deref(), Some("abc123"));
+    }
+}
@@ -0,0 +1,1121 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! reload module: the reload pipeline shared by every trigger (webhook, git
+//! poll, file watch, SQLite mtime, SIGHUP), plus tracking of its outcomes for
+//! `/reload/status` and the reload gauges.
+
+use crate::backoff::Backoff;
+use crate::cache::RouterCache;
+use crate::config::{ReloadConfig, SqliteConfig, validate_links};
+use crate::election::LeaderElection;
+use crate::errors::Error;
+use crate::metrics::Metrics;
+use crate::source::{LinkSnapshot, LinkSource};
+use crate::watch::LinksWatcher;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::oneshot;
+use tokio::sync::{Mutex as TokioMutex, MutexGuard as TokioMutexGuard};
+use tokio::task;
+
+/// Per-link differences between the previously served table and a new one.
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct LinkDiff {
+    /// Codes present only in the new table.
+    pub added: usize,
+    /// Codes present only in the old table.
+    pub removed: usize,
+    /// Codes present in both whose target URL changed.
+    pub modified: usize,
+}
+
+impl LinkDiff {
+    /// Compare `old` against `new`.
+    pub fn between(old: &HashMap<String, String>, new: &HashMap<String, String>) -> Self {
+        let mut diff = LinkDiff::default();
+        for (code, url) in new {
+            match old.get(code) {
+                None => diff.added += 1,
+                Some(old_url) if old_url != url => diff.modified += 1,
+                Some(_) => {}
+            }
+        }
+        diff.removed = old.keys().filter(|code| !new.contains_key(*code)).count();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == LinkDiff::default()
+    }
+}
+
+/// Result of a successful reload.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct ReloadOutcome {
+    /// Whether the reload changed what is served: the fetched ref moved (git
+    /// mode) or the table differs from the previous one (watch mode).
+    pub changed: bool,
+    /// Commit the table was read from (git mode only).
+    pub head: Option<String>,
+    /// Number of links in the new table.
+    pub link_count: usize,
+    /// How the new table differs from the one it replaced.
+    pub diff: LinkDiff,
+}
+
+/// Point-in-time view of reload activity, served as JSON by `/reload/status`.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct ReloadReport {
+    /// Unix time the most recent reload attempt started.
+    pub last_attempt_unix: Option<u64>,
+    /// Unix time of the most recent successful reload.
+    pub last_success_unix: Option<u64>,
+    /// Error from the most recent attempt, cleared by the next success.
+    pub last_error: Option<String>,
+    /// Git commit the served table came from, if known.
+    pub head: Option<String>,
+    /// Number of links currently served.
+    pub link_count: usize,
+    /// Whether a reload is running right now.
+    pub in_progress: bool,
+    /// Reloads failed in a row since the last success.
+    pub consecutive_failures: u32,
+}
+
+/// Seconds since the Unix epoch (0 if the clock is before it).
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Shared, thread-safe record of reload activity. Every update is mirrored
+/// into the corresponding Prometheus gauges.
+#[derive(Clone)]
+pub struct ReloadStatus {
+    inner: Arc<Mutex<ReloadReport>>,
+    metrics: Metrics,
+}
+
+impl ReloadStatus {
+    /// Start tracking from the table loaded at startup.
+    pub fn new(metrics: Metrics, link_count: usize, head: Option<String>) -> Self {
+        metrics.links_total.set(link_count as i64);
+        ReloadStatus {
+            inner: Arc::new(Mutex::new(ReloadReport {
+                head,
+                link_count,
+                ..ReloadReport::default()
+            })),
+            metrics,
+        }
+    }
+
+    /// Current status.
+    pub fn report(&self) -> ReloadReport {
+        self.inner.lock().unwrap().clone()
+    }
+
+    fn begin(&self) {
+        let now = unix_now();
+        let mut report = self.inner.lock().unwrap();
+        report.last_attempt_unix = Some(now);
+        report.in_progress = true;
+        self.metrics.reload_last_attempt_timestamp.set(now as i64);
+        self.metrics.reload_in_progress.set(1);
+    }
+
+    fn finish(&self, result: &Result<ReloadOutcome, Error>) {
+        let mut report = self.inner.lock().unwrap();
+        report.in_progress = false;
+        self.metrics.reload_in_progress.set(0);
+        match result {
+            Ok(outcome) => {
+                let now = unix_now();
+                report.last_success_unix = Some(now);
+                report.last_error = None;
+                if outcome.head.is_some() {
+                    report.head = outcome.head.clone();
+                }
+                report.link_count = outcome.link_count;
+                report.consecutive_failures = 0;
+                self.metrics.reload_last_success_timestamp.set(now as i64);
+                self.metrics.links_total.set(outcome.link_count as i64);
+            }
+            Err(e) => {
+                report.last_error = Some(e.to_string());
+                report.consecutive_failures = report.consecutive_failures.saturating_add(1);
+            }
+        }
+        self.metrics
+            .consecutive_reload_failures
+            .set(report.consecutive_failures as i64);
+    }
+}
+
+/// Callback run after each successful reload; see `Reloader::after_reload`.
+type ReloadHook = Box<dyn Fn(&ReloadOutcome) + Send + Sync>;
+
+/// Everything a reload needs, cheap to clone into spawned tasks.
+#[derive(Clone)]
+pub struct Reloader {
+    pub cache: RouterCache,
+    pub metrics: Metrics,
+    pub status: ReloadStatus,
+    source: Arc<dyn LinkSource>,
+    /// Shared by every clone, so a hook set after the background tasks took
+    /// their copies still applies to them.
+    hook: Arc<OnceLock<ReloadHook>>,
+}
+
+impl Reloader {
+    pub fn new(
+        cache: RouterCache,
+        metrics: Metrics,
+        status: ReloadStatus,
+        source: Arc<dyn LinkSource>,
+    ) -> Self {
+        Reloader {
+            cache,
+            metrics,
+            status,
+            source,
+            hook: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Run `hook` with the outcome of every successful reload from the
+    /// source, whatever triggered it (but not after `install`). Only the
+    /// first hook set is kept.
+    pub fn after_reload(&self, hook: impl Fn(&ReloadOutcome) + Send + Sync + 'static) {
+        let _ = self.hook.set(Box::new(hook));
+    }
+
+    /// Refresh the link table from its source (e.g. fetch and read the
+    /// configured git ref, or re-read the watched file). The current table
+    /// is kept on any failure. Metrics, status and failure logging happen
+    /// here so every trigger gets consistent accounting.
+    pub async fn reload(&self) -> Result<ReloadOutcome, Error> {
+        self.status.begin();
+        let result = self
+            .source
+            .refresh()
+            .await
+            .map(|snapshot| self.apply(snapshot));
+        let result = self.record(result);
+        if let Ok(outcome) = &result
+            && let Some(hook) = self.hook.get()
+        {
+            hook(outcome);
+        }
+        result
+    }
+
+    /// Serve a table that came from somewhere other than the source (a
+    /// peer's pushed snapshot), validated and accounted for like a reload.
+    pub fn install(&self, snapshot: LinkSnapshot) -> Result<ReloadOutcome, Error> {
+        self.status.begin();
+        let result = validate_links(snapshot.links).map(|links| {
+            self.apply(LinkSnapshot {
+                links,
+                revision: snapshot.revision,
+            })
+        });
+        self.record(result)
+    }
+
+    /// Swap a validated table in and describe the change.
+    fn apply(&self, snapshot: LinkSnapshot) -> ReloadOutcome {
+        let previous = self.status.report().head;
+        let link_count = snapshot.links.len();
+        let diff = install_links(&self.cache, snapshot.links);
+        // A source with revisions changed iff its revision moved;
+        // otherwise compare the tables themselves.
+        let changed = match (&previous, &snapshot.revision) {
+            (Some(before), Some(after)) => before != after,
+            _ => !diff.is_empty(),
+        };
+        ReloadOutcome {
+            changed,
+            head: snapshot.revision,
+            link_count,
+            diff,
+        }
+    }
+
+    /// Count and log a finished reload (or install) and update the status.
+    fn record(&self, result: Result<ReloadOutcome, Error>) -> Result<ReloadOutcome, Error> {
+        match &result {
+            Ok(_) => self.metrics.reload_success.inc(),
+            Err(Error::UntrustedCommit { commit, reason }) => {
+                tracing::error!(
+                    commit = %commit,
+                    reason = %reason,
+                    "refusing to reload links from a commit not signed by a trusted key"
+                );
+                self.metrics.reload_fail.inc();
+            }
+            Err(e) => {
+                tracing::warn!(source = %self.source.describe(), error = %e, "reload failed");
+                self.metrics.reload_fail.inc();
+            }
+        }
+        self.status.finish(&result);
+        result
+    }
+}
+
+/// Outcome of a coordinated reload, shared by every trigger that waited on
+/// it (hence the `Arc`: `Error` isn't `Clone`).
+pub type SharedResult = Result<ReloadOutcome, Arc<Error>>;
+
+type Waiter = oneshot::Sender<SharedResult>;
+
+#[derive(Default)]
+struct Flight {
+    /// A reload is currently running.
+    running: bool,
+    /// Triggers waiting for the follow-up run; `Some` once one is queued.
+    queued: Option<Vec<Waiter>>,
+    /// A trigger attached to the queued run has claimed its relay.
+    queued_relays: bool,
+}
+
+/// Single-flight reload coordinator shared by every trigger.
+///
+/// At most one reload runs at a time. A trigger arriving while one runs
+/// queues a single follow-up run (the running one may have fetched before
+/// the push that caused the trigger); every further trigger until that
+/// follow-up starts is folded into it and counted in `reload_coalesced`.
+/// Each trigger's result therefore reflects a reload that started after it.
+#[derive(Clone)]
+pub struct ReloadCoordinator {
+    reloader: Reloader,
+    /// Held for the duration of each run, so anything else that must not
+    /// race a reload (e.g. a write to the links repo) can exclude it too.
+    reload_mutex: Arc<TokioMutex<()>>,
+    flight: Arc<Mutex<Flight>>,
+}
+
+/// A pending trigger; see `ReloadCoordinator::trigger`.
+pub struct Ticket {
+    /// True for the first trigger of its run that asked to relay (see
+    /// `trigger_relaying`), whoever started the run; the relay to peers
+    /// should happen once per run and keys off this.
+    pub relay: bool,
+    done: oneshot::Receiver<SharedResult>,
+}
+
+impl Ticket {
+    /// Wait for the run this trigger was attached to.
+    pub async fn wait(self) -> SharedResult {
+        self.done
+            .await
+            .unwrap_or_else(|_| Err(Arc::new(Error::ReloadAborted)))
+    }
+}
+
+impl ReloadCoordinator {
+    pub fn new(reloader: Reloader, reload_mutex: Arc<TokioMutex<()>>) -> Self {
+        ReloadCoordinator {
+            reloader,
+            reload_mutex,
+            flight: Arc::new(Mutex::new(Flight::default())),
+        }
+    }
+
+    pub fn reloader(&self) -> &Reloader {
+        &self.reloader
+    }
+
+    /// Hold the reload lock, for work that must not interleave with a reload
+    /// (the write API's read-modify-push). Call `reloader().reload()` while
+    /// holding it to serve the result.
+    pub async fn lock(&self) -> TokioMutexGuard<'_, ()> {
+        self.reload_mutex.lock().await
+    }
+
+    /// Request a reload. It starts immediately if none is running; otherwise
+    /// it joins the queued follow-up run. The reload proceeds whether or not
+    /// the returned ticket is awaited.
+    pub fn trigger(&self) -> Ticket {
+        self.attach(false)
+    }
+
+    /// Like `trigger`, for a caller that relays the reload to peers once it
+    /// succeeds. The ticket's `relay` is set for the first such caller of
+    /// each run, even when a poll or another non-relaying trigger started or
+    /// queued that run.
+    pub fn trigger_relaying(&self) -> Ticket {
+        self.attach(true)
+    }
+
+    fn attach(&self, wants_relay: bool) -> Ticket {
+        let (tx, done) = oneshot::channel();
+        let mut flight = self.flight.lock().expect("reload flight lock poisoned");
+        let relay = if !flight.running {
+            flight.running = true;
+            let coordinator = self.clone();
+            task::spawn(async move { coordinator.run(vec![tx]).await });
+            wants_relay
+        } else if let Some(waiters) = flight.queued.as_mut() {
+            waiters.push(tx);
+            self.reloader.metrics.reload_coalesced.inc();
+            let relay = wants_relay && !flight.queued_relays;
+            flight.queued_relays |= wants_relay;
+            relay
+        } else {
+            flight.queued = Some(vec![tx]);
+            flight.queued_relays = wants_relay;
+            wants_relay
+        };
+        Ticket { relay, done }
+    }
+
+    /// Run reloads until no follow-up is queued, handing each result to the
+    /// triggers attached to that run.
+    async fn run(self, mut waiters: Vec<Waiter>) {
+        loop {
+            let result = {
+                let _guard = self.reload_mutex.lock().await;
+                self.reloader.reload().await.map_err(Arc::new)
+            };
+            for waiter in waiters {
+                let _ = waiter.send(result.clone());
+            }
+            let mut flight = self.flight.lock().expect("reload flight lock poisoned");
+            flight.queued_relays = false;
+            match flight.queued.take() {
+                Some(next) => waiters = next,
+                None => {
+                    flight.running = false;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Swap a freshly validated table into `cache` and refresh the shortcode
+/// list used for client-side autocomplete. Returns how the new table differs
+/// from the one it replaced; an identical table is not swapped in at all.
+fn install_links(cache: &RouterCache, links: HashMap<String, String>) -> LinkDiff {
+    let diff = LinkDiff::between(&cache.snapshot(), &links);
+    if diff.is_empty() {
+        return diff;
+    }
+    let mut codes: Vec<String> = links.keys().cloned().collect();
+    codes.sort();
+    let _ = std::fs::write("static_html/shortcodes.txt", codes.join("\n"));
+    cache.swap(links);
+    diff
+}
+
+/// Convert a configured poll interval into a `Duration`, treating `None` or
+/// `0` as "polling disabled". Kept side-effect free so it can be unit
+/// tested without touching tokio's clock.
+pub fn poll_interval(poll_interval_secs: Option<u64>) -> Option<Duration> {
+    match poll_interval_secs {
+        None | Some(0) => None,
+        Some(secs) => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// Spawn the background poll reload task, if `poll_interval_secs` enables
+/// it. Polls go through the shared `coordinator`, so a poll and a
+/// webhook-triggered reload never run `git fetch` concurrently. `kind` names
+/// the polled source (`"git"`, `"http"`, `"sqlite"`) in log messages, which
+/// read e.g. `git-poll reload enabled`.
+///
+/// While reloads keep failing (by any trigger), the wait before the next poll
+/// backs off exponentially from the interval up to `max_backoff_secs`, with
+/// jitter; the first success returns to the normal interval. From
+/// `failure_alert_threshold` consecutive failures on, each failed poll is
+/// logged at error level so alerting can key off it.
+///
+/// With an `election`, polls are skipped while another node is the leader.
+pub fn spawn_poll_task(
+    kind: &'static str,
+    coordinator: ReloadCoordinator,
+    poll_interval_secs: Option<u64>,
+    max_backoff_secs: u64,
+    failure_alert_threshold: u32,
+    election: Option<LeaderElection>,
+) {
+    let Some(interval) = poll_interval(poll_interval_secs) else {
+        tracing::info!("{kind}-poll reload disabled (poll_interval_secs unset or 0)");
+        return;
+    };
+    let backoff = Backoff::new(interval, Duration::from_secs(max_backoff_secs));
+    tracing::info!(
+        interval_secs = interval.as_secs(),
+        max_backoff_secs = backoff.max.as_secs(),
+        "{kind}-poll reload enabled"
+    );
+    task::spawn(async move {
+        // Links are already fresh from startup, so the first poll waits a
+        // full interval.
+        let mut delay = interval;
+        loop {
+            tokio::time::sleep(delay).await;
+            if let Some(election) = &election
+                && !election.is_leader()
+            {
+                tracing::debug!("{kind}-poll reload: skipped; another node is the leader");
+                delay = interval;
+                continue;
+            }
+            let result = coordinator.trigger().wait().await;
+            let failures = coordinator.reloader.status.report().consecutive_failures;
+            delay = match result {
+                Ok(outcome) => {
+                    if outcome.changed {
+                        tracing::info!("{kind}-poll reload: links changed");
+                    }
+                    interval
+                }
+                Err(_) if failures == 0 => interval,
+                Err(_) => {
+                    let delay = backoff.jittered(failures);
+                    if failures >= failure_alert_threshold {
+                        tracing::error!(
+                            consecutive_failures = failures,
+                            retry_in_secs = delay.as_secs(),
+                            "{kind}-poll reload: still failing; links are going stale"
+                        );
+                    } else {
+                        tracing::warn!(
+                            consecutive_failures = failures,
+                            retry_in_secs = delay.as_secs(),
+                            "{kind}-poll reload: fetch or reload failed; backing off"
+                        );
+                    }
+                    delay
+                }
+            };
+        }
+    });
+}
+
+/// Spawn the watch-mode reload task: reload the links file whenever it, or
+/// anything in its include directory, changes on disk. Goes through the
+/// shared `coordinator` so a file-triggered and a webhook-triggered reload
+/// never interleave.
+pub fn spawn_watch_task(
+    coordinator: ReloadCoordinator,
+    reload: &ReloadConfig,
+) -> Result<(), Error> {
+    let path = reload.watch_path.clone();
+    let debounce_ms = reload.debounce_ms;
+    let mut watcher = LinksWatcher::new(
+        Path::new(&path),
+        reload.include_dir.as_deref().map(Path::new),
+        Duration::from_millis(debounce_ms),
+    )?;
+    tracing::info!(
+        path = %path,
+        include_dir = reload.include_dir.as_deref().unwrap_or(""),
+        debounce_ms,
+        "file-watch reload enabled"
+    );
+    task::spawn(async move {
+        while watcher.changed().await {
+            match coordinator.trigger().wait().await {
+                Ok(outcome) if outcome.changed => {
+                    tracing::info!("file-watch reload: links changed")
+                }
+                Ok(_) => {}
+                Err(_) => tracing::warn!("file-watch reload: reload failed"),
+            }
+        }
+        tracing::warn!("file-watch reload stopped: watcher shut down");
+    });
+    Ok(())
+}
+
+/// Modification time and length of each of `paths` (`None` for a missing
+/// file), compared between checks to spot writes.
+pub fn file_stamps(paths: &[String]) -> Vec<Option<(SystemTime, u64)>> {
+    paths
+        .iter()
+        .map(|p| {
+            let meta = std::fs::metadata(p).ok()?;
+            Some((meta.modified().ok()?, meta.len()))
+        })
+        .collect()
+}
+
+/// Spawn the SQLite `mtime` reload task: check the database file and its
+/// write-ahead log every `check_interval_ms`, and reload when either has
+/// changed since the last check. A write in WAL mode may only touch the
+/// `-wal` file until the next checkpoint, hence watching both.
+pub fn spawn_mtime_task(coordinator: ReloadCoordinator, sqlite: &SqliteConfig) {
+    let paths = vec![sqlite.path.clone(), format!("{}-wal", sqlite.path)];
+    let interval = Duration::from_millis(sqlite.check_interval_ms.max(1));
+    tracing::info!(
+        path = %sqlite.path,
+        check_interval_ms = interval.as_millis() as u64,
+        "sqlite mtime reload enabled"
+    );
+    task::spawn(async move {
+        let mut last = file_stamps(&paths);
+        loop {
+            tokio::time::sleep(interval).await;
+            let stamps = file_stamps(&paths);
+            if stamps == last {
+                continue;
+            }
+            last = stamps;
+            match coordinator.trigger().wait().await {
+                Ok(outcome) if outcome.changed => {
+                    tracing::info!("sqlite mtime reload: links changed")
+                }
+                Ok(_) => {}
+                Err(_) => tracing::warn!("sqlite mtime reload: reload failed"),
+            }
+        }
+    });
+}
+
+/// Spawn the SIGHUP handler: each SIGHUP runs the same reload as the poll
+/// task (without relaying), coordinated with every other trigger. This is
+/// the local, webhook-free way to force a reload.
+pub fn spawn_sighup_task(coordinator: ReloadCoordinator) -> Result<(), Error> {
+    let mut hangups = signal(SignalKind::hangup())?;
+    task::spawn(async move {
+        while hangups.recv().await.is_some() {
+            tracing::info!("SIGHUP received; reloading links");
+            match coordinator.trigger().wait().await {
+                Ok(outcome) if outcome.changed => tracing::info!("SIGHUP reload: links changed"),
+                Ok(_) => tracing::info!("SIGHUP reload: links unchanged"),
+                Err(_) => tracing::warn!("SIGHUP reload: reload failed"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Shared fixtures for tests that exercise real git reloads.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use crate::config::{GitConfig, LinkFormat};
+    use crate::git::{self, GIT_BINARY};
+    use crate::source::GitSource;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub fn run_git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new(GIT_BINARY)
+            .current_dir(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// A throwaway "origin" repository plus a bare repository that fetches
+    /// from it, mirroring the production layout. Removed on drop.
+    pub struct GitFixture {
+        pub root: PathBuf,
+        pub git: GitConfig,
+    }
+
+    impl GitFixture {
+        pub fn new(links: &str) -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let root = std::env::temp_dir().join(format!(
+                "redirective-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let origin = root.join("origin");
+            std::fs::create_dir_all(&origin).unwrap();
+            run_git(&origin, &["init", "-q", "-b", "main"]);
+            let fixture = GitFixture {
+                git: GitConfig {
+                    repo_dir: root.join("links.git").to_string_lossy().into_owned(),
+                    ..GitConfig::default()
+                },
+                root,
+            };
+            fixture.commit(links);
+            run_git(&fixture.root, &["init", "-q", "--bare", "links.git"]);
+            let origin_url = origin.to_string_lossy().into_owned();
+            let bare = PathBuf::from(&fixture.git.repo_dir);
+            run_git(&bare, &["remote", "add", "origin", &origin_url]);
+            run_git(&bare, &["fetch", "-q", "origin"]);
+            fixture
+        }
+
+        /// A git-mode reloader over this fixture, starting from `cache`.
+        /// Like startup, it starts out knowing the ref's current commit.
+        pub fn reloader(&self, cache: RouterCache, metrics: Metrics) -> Reloader {
+            let head = git::resolve_ref(GIT_BINARY, &self.git).ok();
+            let status = ReloadStatus::new(metrics.clone(), 0, head);
+            let source = GitSource::new(self.git.clone(), LinkFormat::Yaml);
+            Reloader::new(cache, metrics, status, Arc::new(source))
+        }
+
+        /// Commit `links` as `links.yaml` in the origin repository.
+        pub fn commit(&self, links: &str) {
+            let origin = self.root.join("origin");
+            std::fs::write(origin.join("links.yaml"), links).unwrap();
+            run_git(&origin, &["add", "links.yaml"]);
+            run_git(
+                &origin,
+                &["commit", "-q", "--allow-empty", "-m", "update links"],
+            );
+        }
+
+        /// Let the origin repository accept pushes to its checked-out branch
+        /// (as a bare forge repository would).
+        pub fn accept_pushes(&self) {
+            let origin = self.root.join("origin");
+            run_git(
+                &origin,
+                &["config", "receive.denyCurrentBranch", "updateInstead"],
+            );
+        }
+
+        /// Commit `links` signed with the SSH key at `key`.
+        pub fn commit_signed(&self, links: &str, key: &Path) {
+            let origin = self.root.join("origin");
+            std::fs::write(origin.join("links.yaml"), links).unwrap();
+            run_git(&origin, &["add", "links.yaml"]);
+            let signing_key = format!("user.signingkey={}", key.display());
+            run_git(
+                &origin,
+                &[
+                    "-c",
+                    "gpg.format=ssh",
+                    "-c",
+                    &signing_key,
+                    "commit",
+                    "-q",
+                    "-S",
+                    "-m",
+                    "signed links",
+                ],
+            );
+        }
+
+        /// Generate an SSH signing key, returning its private key path and
+        /// `SHA256:` fingerprint.
+        pub fn ssh_key(&self, name: &str) -> (PathBuf, String) {
+            let key = self.root.join(name);
+            let status = std::process::Command::new("ssh-keygen")
+                .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+                .arg(&key)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let listing = std::process::Command::new("ssh-keygen")
+                .arg("-lf")
+                .arg(key.with_extension("pub"))
+                .output()
+                .unwrap();
+            let listing = String::from_utf8(listing.stdout).unwrap();
+            let fingerprint = listing.split_whitespace().nth(1).unwrap().to_string();
+            (key, fingerprint)
+        }
+    }
+
+    impl Drop for GitFixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::GitFixture;
+    use super::*;
+    use crate::config::{LinkFormat, ReloadMode};
+    use crate::metrics::init_metrics;
+    use crate::source::{FileSource, SqliteSource};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_reload_reads_links_from_fetched_ref() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let cache = RouterCache::new(HashMap::new());
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(cache.clone(), metrics.clone());
+        let first = reloader.reload().await.unwrap();
+        assert!(!first.changed);
+        assert_eq!(cache.lookup("foo"), Some("https://example.com".to_string()));
+
+        // A new upstream commit is picked up by the next fetch, and the
+        // moved ref is reported as a change.
+        fixture.commit("bar: https://example.org\n");
+        let second = reloader.reload().await.unwrap();
+        assert!(second.changed);
+        assert_ne!(second.head, first.head);
+        assert_eq!(cache.lookup("foo"), None);
+        assert_eq!(cache.lookup("bar"), Some("https://example.org".to_string()));
+        assert_eq!(metrics.reload_success.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_old_table_when_ref_has_invalid_links() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let cache = RouterCache::new(HashMap::new());
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(cache.clone(), metrics.clone());
+        reloader.reload().await.unwrap();
+        fixture.commit("foo: \"\"\n");
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(metrics.reload_fail.get(), 1);
+        assert_eq!(cache.lookup("foo"), Some("https://example.com".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_after_reload_hook_fires_on_reload_but_not_install() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let cache = RouterCache::new(HashMap::new());
+        let reloader = fixture.reloader(cache.clone(), init_metrics());
+        let fired = Arc::new(AtomicUsize::new(0));
+        let count = fired.clone();
+        reloader.after_reload(move |_| {
+            count.fetch_add(1, Ordering::SeqCst);
+        });
+        reloader.reload().await.unwrap();
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+
+        // A table pushed by a peer is installed quietly, and is validated
+        // like any other.
+        let pushed = HashMap::from([("bar".to_string(), "https://example.org".to_string())]);
+        let outcome = reloader
+            .install(LinkSnapshot {
+                links: pushed,
+                revision: Some("def456".to_string()),
+            })
+            .unwrap();
+        assert_eq!(outcome.head.as_deref(), Some("def456"));
+        assert_eq!(cache.lookup("bar"), Some("https://example.org".to_string()));
+        let invalid = HashMap::from([("bar".to_string(), String::new())]);
+        assert!(
+            reloader
+                .install(LinkSnapshot {
+                    links: invalid,
+                    revision: None,
+                })
+                .is_err()
+        );
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reload_fails_when_links_path_missing_from_ref() {
+        let mut fixture = GitFixture::new("foo: https://example.com\n");
+        fixture.git.links_path = "missing.yaml".to_string();
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(metrics.reload_fail.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reload_accepts_commit_signed_by_trusted_key() {
+        let mut fixture = GitFixture::new("foo: https://example.com\n");
+        let (key, fingerprint) = fixture.ssh_key("trusted");
+        fixture.git.trusted_signing_keys = vec![fingerprint];
+        fixture.commit_signed("bar: https://example.org\n", &key);
+        let cache = RouterCache::new(HashMap::new());
+        let reloader = fixture.reloader(cache.clone(), init_metrics());
+        assert!(reloader.reload().await.is_ok());
+        assert_eq!(cache.lookup("bar"), Some("https://example.org".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_reload_rejects_unsigned_or_untrusted_commit() {
+        let mut fixture = GitFixture::new("foo: https://example.com\n");
+        let (_, trusted) = fixture.ssh_key("trusted");
+        let (rogue, _) = fixture.ssh_key("rogue");
+        fixture.git.trusted_signing_keys = vec![trusted];
+        let cache = RouterCache::new(HashMap::new());
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(cache.clone(), metrics.clone());
+
+        // The fixture's initial commit is unsigned.
+        assert!(matches!(
+            reloader.reload().await,
+            Err(Error::UntrustedCommit { .. })
+        ));
+        fixture.commit_signed("evil: https://evil.example\n", &rogue);
+        assert!(matches!(
+            reloader.reload().await,
+            Err(Error::UntrustedCommit { .. })
+        ));
+        assert_eq!(metrics.reload_fail.get(), 2);
+        assert_eq!(metrics.reload_success.get(), 0);
+        assert_eq!(cache.lookup("evil"), None);
+    }
+
+    /// A fresh directory under the system temp dir.
+    fn scratch_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "redirective-reload-{}-{}",
+            std::process::id(),
+            fastrand::u64(..)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn watch_config(path: &Path) -> ReloadConfig {
+        ReloadConfig {
+            mode: ReloadMode::Watch,
+            watch_path: path.to_string_lossy().into_owned(),
+            debounce_ms: 50,
+            ..ReloadConfig::default()
+        }
+    }
+
+    fn watch_reloader(path: &Path, cache: RouterCache, metrics: Metrics) -> Reloader {
+        let status = ReloadStatus::new(metrics.clone(), 0, None);
+        let path = path.to_string_lossy();
+        let source = FileSource::new(&path, LinkFormat::resolve(None, &path));
+        Reloader::new(cache, metrics, status, Arc::new(source))
+    }
+
+    #[tokio::test]
+    async fn test_burst_of_triggers_coalesces_into_one_follow_up() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let reload_mutex = Arc::new(TokioMutex::new(()));
+        let coordinator = ReloadCoordinator::new(reloader, reload_mutex.clone());
+        // Hold the mutex so the first run is stuck "in progress" while the
+        // rest of the burst arrives.
+        let guard = reload_mutex.lock().await;
+        let tickets: Vec<Ticket> = (0..10).map(|_| coordinator.trigger()).collect();
+        drop(guard);
+        for ticket in tickets {
+            assert!(ticket.wait().await.is_ok());
+        }
+        // The running reload plus a single follow-up for the other nine.
+        assert_eq!(metrics.reload_success.get(), 2);
+        assert_eq!(metrics.reload_coalesced.get(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_after_idle_starts_a_new_run() {
+        let fixture = GitFixture::new("foo: https://example.com\n");
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let coordinator = ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(())));
+        coordinator.trigger().wait().await.unwrap();
+        fixture.commit("bar: https://example.org\n");
+        let outcome = coordinator.trigger().wait().await.unwrap();
+        assert!(outcome.changed);
+        assert_eq!(metrics.reload_success.get(), 2);
+        assert_eq!(metrics.reload_coalesced.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_watch_mode_reloads_when_file_changes() {
+        let dir = scratch_dir();
+        let path = dir.join("links.yaml");
+        std::fs::write(&path, "foo: https://example.com\n").unwrap();
+        let cache = RouterCache::new(HashMap::new());
+        let metrics = init_metrics();
+        let reloader = watch_reloader(&path, cache.clone(), metrics.clone());
+        spawn_watch_task(
+            ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(()))),
+            &watch_config(&path),
+        )
+        .unwrap();
+        std::fs::write(&path, "bar: https://example.org\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while cache.lookup("bar").is_none() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(cache.lookup("bar"), Some("https://example.org".to_string()));
+        assert_eq!(metrics.reload_fail.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_watch_mode_reloads_when_included_file_changes() {
+        let dir = scratch_dir();
+        let path = dir.join("links.yaml");
+        std::fs::write(&path, "foo: https://example.com\n").unwrap();
+        let include_dir = dir.join("links.d");
+        std::fs::create_dir_all(include_dir.join("team")).unwrap();
+        let include = include_dir.to_string_lossy().into_owned();
+        let cache = RouterCache::new(HashMap::new());
+        let metrics = init_metrics();
+        let status = ReloadStatus::new(metrics.clone(), 0, None);
+        let source = FileSource::new(&path.to_string_lossy(), LinkFormat::Yaml)
+            .with_include_dir(Some(include.clone()));
+        let reloader = Reloader::new(cache.clone(), metrics.clone(), status, Arc::new(source));
+        spawn_watch_task(
+            ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(()))),
+            &ReloadConfig {
+                include_dir: Some(include),
+                ..watch_config(&path)
+            },
+        )
+        .unwrap();
+        std::fs::write(
+            include_dir.join("team/more.csv"),
+            "code,url\nbar,https://example.org\n",
+        )
+        .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while cache.lookup("bar").is_none() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(cache.lookup("bar"), Some("https://example.org".to_string()));
+        assert_eq!(cache.lookup("foo"), Some("https://example.com".to_string()));
+        assert_eq!(metrics.reload_fail.get(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_watch_mode_reload_rereads_file_without_git() {
+        let dir = scratch_dir();
+        let path = dir.join("links.yaml");
+        std::fs::write(&path, "foo: https://example.com\n").unwrap();
+        let cache = RouterCache::new(HashMap::new());
+        let reloader = watch_reloader(&path, cache.clone(), init_metrics());
+        let outcome = reloader.reload().await.unwrap();
+        assert!(outcome.changed);
+        assert_eq!(outcome.head, None);
+        assert_eq!(cache.lookup("foo"), Some("https://example.com".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_mtime_task_reloads_after_write() {
+        let dir = scratch_dir();
+        let path = dir.join("links.db");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE links (code TEXT PRIMARY KEY, url TEXT NOT NULL);
+             INSERT INTO links VALUES ('foo', 'https://example.com');",
+        )
+        .unwrap();
+        let sqlite = SqliteConfig {
+            path: path.to_string_lossy().into_owned(),
+            check_interval_ms: 20,
+            ..SqliteConfig::default()
+        };
+        let cache = RouterCache::new(HashMap::new());
+        let metrics = init_metrics();
+        let status = ReloadStatus::new(metrics.clone(), 0, None);
+        let source = Arc::new(SqliteSource::new(&sqlite));
+        let reloader = Reloader::new(cache.clone(), metrics.clone(), status, source);
+        spawn_mtime_task(
+            ReloadCoordinator::new(reloader, Arc::new(TokioMutex::new(()))),
+            &sqlite,
+        );
+        // Let the task take its first reading before the write.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        conn.execute(
+            "INSERT INTO links VALUES ('bar', 'https://example.org')",
+            [],
+        )
+        .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while cache.lookup("bar").is_none() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(cache.lookup("bar"), Some("https://example.org".to_string()));
+        assert_eq!(cache.lookup("foo"), Some("https://example.com".to_string()));
+        assert_eq!(metrics.reload_fail.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_table_is_not_swapped_in() {
+        let dir = scratch_dir();
+        let path = dir.join("links.yaml");
+        std::fs::write(&path, "foo: https://example.com\n").unwrap();
+        let cache = RouterCache::new(HashMap::new());
+        let reloader = watch_reloader(&path, cache.clone(), init_metrics());
+        reloader.reload().await.unwrap();
+        let before = cache.snapshot();
+        let outcome = reloader.reload().await.unwrap();
+        assert!(!outcome.changed);
+        assert!(Arc::ptr_eq(&before, &cache.snapshot()));
+    }
+
+    #[tokio::test]
+    async fn test_status_tracks_success_then_failure() {
+        let fixture = GitFixture::new("foo: https://example.com\nbar: https://example.org\n");
+        let metrics = init_metrics();
+        let reloader = fixture.reloader(RouterCache::new(HashMap::new()), metrics.clone());
+        let outcome = reloader.reload().await.unwrap();
+        let report = reloader.status.report();
+        assert_eq!(report.link_count, 2);
+        assert_eq!(report.head, outcome.head);
+        assert!(report.last_success_unix.is_some());
+        assert_eq!(report.last_error, None);
+        assert!(!report.in_progress);
+        assert_eq!(metrics.links_total.get(), 2);
+
+        fixture.commit("foo: \"\"\n");
+        assert!(reloader.reload().await.is_err());
+        let report = reloader.status.report();
+        // A failure records the error but keeps describing the served table.
+        assert!(report.last_error.unwrap().contains("invalid"));
+        assert_eq!(report.head, outcome.head);
+        assert_eq!(report.link_count, 2);
+        assert_eq!(metrics.reload_in_progress.get(), 0);
+
+        // Failures accumulate until the next success resets them.
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(reloader.status.report().consecutive_failures, 2);
+        assert_eq!(metrics.consecutive_reload_failures.get(), 2);
+        fixture.commit("foo: https://example.com\n");
+        reloader.reload().await.unwrap();
+        assert_eq!(reloader.status.report().consecutive_failures, 0);
+        assert_eq!(metrics.consecutive_reload_failures.get(), 0);
+    }
+
+    #[test]
+    fn test_link_diff_counts_added_removed_modified() {
+        let old: HashMap<String, String> = [("a", "1"), ("b", "2"), ("c", "3")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let new: HashMap<String, String> = [("a", "1"), ("b", "20"), ("d", "4"), ("e", "5")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let diff = LinkDiff::between(&old, &new);
+        assert_eq!(
+            diff,
+            LinkDiff {
+                added: 2,
+                removed: 1,
+                modified: 1
+            }
+        );
+        assert!(LinkDiff::between(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_poll_interval_none_when_unset() {
+        assert_eq!(poll_interval(None), None);
+    }
+
+    #[test]
+    fn test_poll_interval_none_when_zero() {
+        assert_eq!(poll_interval(Some(0)), None);
+    }
+
+    #[test]
+    fn test_poll_interval_some_when_positive() {
+        assert_eq!(poll_interval(Some(60)), Some(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,273 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! signature module: verifies that webhook requests come from a forge (or a
+//! peer node) holding the shared webhook secret, that relays come from a
+//! node holding the cluster secret, and that write API requests carry the
+//! API token.
+
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// GitHub (and Gitea, for compatibility): `sha256=<hex HMAC of the body>`.
+pub const HUB_SIGNATURE_HEADER: &str = "x-hub-signature-256";
+/// Gitea: bare `<hex HMAC of the body>`.
+pub const GITEA_SIGNATURE_HEADER: &str = "x-gitea-signature";
+/// GitLab: the secret token itself, sent verbatim (GitLab does not sign).
+pub const GITLAB_TOKEN_HEADER: &str = "x-gitlab-token";
+/// Peer relays: `t=<unix time>,n=<nonce>,sha256=<hex HMAC>`; see `sign_relay`.
+pub const RELAY_SIGNATURE_HEADER: &str = "x-redirective-relay-signature";
+
+/// The timestamp and nonce of a relay whose signature verified, for the
+/// caller's replay check.
+#[derive(Debug, PartialEq)]
+pub struct RelayStamp {
+    pub timestamp: u64,
+    pub nonce: String,
+}
+
+fn mac(secret: &str) -> HmacSha256 {
+    HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length")
+}
+
+/// Compute the `X-Hub-Signature-256` value for `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = mac(secret);
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// HMAC input for a relay: the timestamp and nonce are signed along with the
+/// body so neither can be swapped out of a captured request.
+fn relay_message(timestamp: u64, nonce: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}.{}.", timestamp, nonce).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+/// Compute the `X-Redirective-Relay-Signature` value for a relay of `body`
+/// sent at `timestamp` (Unix seconds). `nonce` must be unique per relay.
+pub fn sign_relay(secret: &str, timestamp: u64, nonce: &str, body: &[u8]) -> String {
+    let mut mac = mac(secret);
+    mac.update(&relay_message(timestamp, nonce, body));
+    format!(
+        "t={},n={},sha256={}",
+        timestamp,
+        nonce,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// Verify a relay's `X-Redirective-Relay-Signature` against the cluster
+/// `secret`. Returns its stamp if the HMAC matches and the timestamp is
+/// within `window_secs` of `now` either way (clock skew); `None` if the
+/// header is missing, malformed, stale or forged. Rejecting a stamp that was
+/// already used is left to the caller, which owns the seen-nonce state.
+pub fn verify_relay(
+    secret: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+    now: u64,
+    window_secs: u64,
+) -> Option<RelayStamp> {
+    let value = headers.get(RELAY_SIGNATURE_HEADER)?.to_str().ok()?;
+    let (mut timestamp, mut nonce, mut digest) = (None, None, None);
+    for field in value.split(',') {
+        match field.trim().split_once('=') {
+            Some(("t", t)) => timestamp = t.parse::<u64>().ok(),
+            Some(("n", n)) if !n.is_empty() => nonce = Some(n),
+            Some(("sha256", d)) => digest = Some(d),
+            _ => {}
+        }
+    }
+    let (timestamp, nonce, digest) = (timestamp?, nonce?, digest?);
+    if now.abs_diff(timestamp) > window_secs
+        || !hmac_matches(secret, &relay_message(timestamp, nonce, body), digest)
+    {
+        return None;
+    }
+    Some(RelayStamp {
+        timestamp,
+        nonce: nonce.to_string(),
+    })
+}
+
+/// Check a hex-encoded HMAC-SHA256 of `body` in constant time.
+fn hmac_matches(secret: &str, body: &[u8], hex_digest: &str) -> bool {
+    let Ok(expected) = hex::decode(hex_digest.trim()) else {
+        return false;
+    };
+    let mut mac = mac(secret);
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Returns true if the request carries `Authorization: Bearer <token>`.
+pub fn verify_bearer(token: &str, headers: &HeaderMap) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.trim().as_bytes(), token.as_bytes()))
+}
+
+/// Returns true if the request proves knowledge of `secret` via any of the
+/// supported schemes: an `X-Hub-Signature-256` or `X-Gitea-Signature` HMAC
+/// of the raw `body`, or an `X-Gitlab-Token` equal to the secret.
+pub fn verify_webhook(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(sig) = header(HUB_SIGNATURE_HEADER) {
+        return sig
+            .strip_prefix("sha256=")
+            .is_some_and(|digest| hmac_matches(secret, body, digest));
+    }
+    if let Some(sig) = header(GITEA_SIGNATURE_HEADER) {
+        return hmac_matches(secret, body, sig);
+    }
+    if let Some(token) = header(GITLAB_TOKEN_HEADER) {
+        return constant_time_eq(token.as_bytes(), secret.as_bytes());
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "It's a Secret to Everybody";
+    const BODY: &[u8] = b"Hello, World!";
+    // Test vector from GitHub's "Validating webhook deliveries" docs.
+    const GITHUB_SIG: &str =
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_sign_matches_github_test_vector() {
+        assert_eq!(sign(SECRET, BODY), GITHUB_SIG);
+    }
+
+    #[test]
+    fn test_verify_hub_signature() {
+        assert!(verify_webhook(
+            SECRET,
+            &headers(HUB_SIGNATURE_HEADER, GITHUB_SIG),
+            BODY
+        ));
+        assert!(!verify_webhook(
+            SECRET,
+            &headers(HUB_SIGNATURE_HEADER, GITHUB_SIG),
+            b"Hello, World?"
+        ));
+        assert!(!verify_webhook(
+            "other secret",
+            &headers(HUB_SIGNATURE_HEADER, GITHUB_SIG),
+            BODY
+        ));
+        // The prefix is required.
+        let bare = GITHUB_SIG.trim_start_matches("sha256=");
+        assert!(!verify_webhook(
+            SECRET,
+            &headers(HUB_SIGNATURE_HEADER, bare),
+            BODY
+        ));
+    }
+
+    #[test]
+    fn test_verify_gitea_signature() {
+        let bare = GITHUB_SIG.trim_start_matches("sha256=");
+        assert!(verify_webhook(
+            SECRET,
+            &headers(GITEA_SIGNATURE_HEADER, bare),
+            BODY
+        ));
+        assert!(!verify_webhook(
+            SECRET,
+            &headers(GITEA_SIGNATURE_HEADER, "not hex"),
+            BODY
+        ));
+    }
+
+    #[test]
+    fn test_verify_gitlab_token() {
+        assert!(verify_webhook(
+            SECRET,
+            &headers(GITLAB_TOKEN_HEADER, SECRET),
+            BODY
+        ));
+        assert!(!verify_webhook(
+            SECRET,
+            &headers(GITLAB_TOKEN_HEADER, "It's a Secret to Nobody"),
+            BODY
+        ));
+    }
+
+    #[test]
+    fn test_unsigned_request_rejected() {
+        assert!(!verify_webhook(SECRET, &HeaderMap::new(), BODY));
+    }
+
+    #[test]
+    fn test_verify_relay_round_trip() {
+        let value = sign_relay(SECRET, 1_000, "abc", BODY);
+        let h = headers(RELAY_SIGNATURE_HEADER, &value);
+        assert_eq!(
+            verify_relay(SECRET, &h, BODY, 1_010, 300),
+            Some(RelayStamp {
+                timestamp: 1_000,
+                nonce: "abc".to_string()
+            })
+        );
+        assert_eq!(verify_relay("other secret", &h, BODY, 1_010, 300), None);
+        assert_eq!(verify_relay(SECRET, &h, b"tampered", 1_010, 300), None);
+        assert_eq!(
+            verify_relay(SECRET, &HeaderMap::new(), BODY, 1_010, 300),
+            None
+        );
+    }
+
+    #[test]
+    fn test_verify_relay_enforces_window() {
+        let h = headers(
+            RELAY_SIGNATURE_HEADER,
+            &sign_relay(SECRET, 1_000, "abc", BODY),
+        );
+        assert!(verify_relay(SECRET, &h, BODY, 1_300, 300).is_some());
+        assert!(verify_relay(SECRET, &h, BODY, 700, 300).is_some());
+        assert!(verify_relay(SECRET, &h, BODY, 1_301, 300).is_none());
+        assert!(verify_relay(SECRET, &h, BODY, 699, 300).is_none());
+    }
+
+    #[test]
+    fn test_verify_relay_rejects_swapped_stamp() {
+        let value = sign_relay(SECRET, 1_000, "abc", BODY);
+        let swapped = value.replace("t=1000", "t=1001");
+        let h = headers(RELAY_SIGNATURE_HEADER, &swapped);
+        assert_eq!(verify_relay(SECRET, &h, BODY, 1_000, 300), None);
+        let swapped = value.replace("n=abc", "n=abd");
+        let h = headers(RELAY_SIGNATURE_HEADER, &swapped);
+        assert_eq!(verify_relay(SECRET, &h, BODY, 1_000, 300), None);
+    }
+
+    #[test]
+    fn test_verify_bearer() {
+        let h = headers("authorization", "Bearer s3cret");
+        assert!(verify_bearer("s3cret", &h));
+        assert!(!verify_bearer("other", &h));
+        assert!(!verify_bearer(
+            "s3cret",
+            &headers("authorization", "Basic s3cret")
+        ));
+        assert!(!verify_bearer("s3cret", &HeaderMap::new()));
+    }
+}
@@ -0,0 +1,770 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! source module: where the link table comes from. A `LinkSource` loads the
+//! full table (a file on disk, in a git ref's tree or behind a URL, in one of
+//! the supported document formats, or a SQLite table); everything downstream
+//! of it is shared.
+
+use crate::client::{HttpsClient, https_client};
+use crate::config::{
+    GitConfig, LinkFormat, ReloadMode, RemoteConfig, ServiceConfig, SqliteConfig, parse_links,
+    validate_links,
+};
+use crate::errors::Error;
+use crate::git::{self, GIT_BINARY};
+use hyper::StatusCode;
+use hyper::header::{self, HeaderValue};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Boxed future returned by `LinkSource` methods (keeps the trait object
+/// safe without pulling in `async-trait`).
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A validated link table plus where it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkSnapshot {
+    /// Mapping of codes to target URLs.
+    pub links: HashMap<String, String>,
+    /// Version of the source the table was read from (a git commit), if the
+    /// source has one.
+    pub revision: Option<String>,
+}
+
+/// A place the link table can be loaded from.
+pub trait LinkSource: Send + Sync {
+    /// Human-readable description for logs, e.g. `git origin/main:links.yaml`.
+    fn describe(&self) -> String;
+
+    /// Load the table as the source has it now, without refreshing from
+    /// upstream. Used at startup.
+    fn current(&self) -> BoxFuture<'_, Result<LinkSnapshot, Error>>;
+
+    /// Refresh from upstream (e.g. `git fetch`), then load. Used by every
+    /// reload trigger. Sources with nothing to refresh just load.
+    fn refresh(&self) -> BoxFuture<'_, Result<LinkSnapshot, Error>> {
+        self.current()
+    }
+}
+
+impl LinkFormat {
+    /// Infer the format from a file extension, if it's a known one.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "yaml" | "yml" => Some(LinkFormat::Yaml),
+            "json" => Some(LinkFormat::Json),
+            "toml" => Some(LinkFormat::Toml),
+            "csv" => Some(LinkFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// The configured format, else the one `path`'s extension implies, else
+    /// YAML.
+    pub fn resolve(configured: Option<LinkFormat>, path: &str) -> Self {
+        configured
+            .or_else(|| LinkFormat::from_path(path))
+            .unwrap_or(LinkFormat::Yaml)
+    }
+
+    /// Parse and validate a links document in this format.
+    pub fn parse(self, content: &str) -> Result<HashMap<String, String>, Error> {
+        match self {
+            LinkFormat::Yaml => parse_links(content),
+            LinkFormat::Json => {
+                let links: HashMap<String, String> = serde_json::from_str(content)
+                    .map_err(|e| Error::Config(format!("Failed to parse JSON: {}", e)))?;
+                validate_links(links)
+            }
+            LinkFormat::Toml => {
+                let links: HashMap<String, String> = toml::from_str(content)
+                    .map_err(|e| Error::Config(format!("Failed to parse TOML: {}", e)))?;
+                validate_links(links)
+            }
+            LinkFormat::Csv => validate_links(parse_csv(content)?),
+        }
+    }
+}
+
+/// Parse `code,url` rows. A first row whose first column is `code` or
+/// `shortcode` is taken as a header; columns past the second, blank lines and
+/// lines starting with `#` are ignored, so spreadsheet exports work as-is.
+fn parse_csv(content: &str) -> Result<HashMap<String, String>, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let mut links = HashMap::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| Error::Config(format!("Failed to parse CSV: {}", e)))?;
+        let code = record.get(0).unwrap_or("");
+        if index == 0
+            && (code.eq_ignore_ascii_case("code") || code.eq_ignore_ascii_case("shortcode"))
+        {
+            continue;
+        }
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let line = record.position().map_or(index as u64 + 1, |p| p.line());
+        let Some(url) = record.get(1) else {
+            return Err(Error::Config(format!(
+                "CSV line {}: expected code,url",
+                line
+            )));
+        };
+        if links.insert(code.to_string(), url.to_string()).is_some() {
+            return Err(Error::Config(format!(
+                "CSV line {}: duplicate code '{}'",
+                line, code
+            )));
+        }
+    }
+    Ok(links)
+}
+
+/// A links file on local disk, plus any links files in an include
+/// directory.
+pub struct FileSource {
+    path: String,
+    format: LinkFormat,
+    include_dir: Option<String>,
+}
+
+impl FileSource {
+    pub fn new(path: &str, format: LinkFormat) -> Self {
+        FileSource {
+            path: path.to_string(),
+            format,
+            include_dir: None,
+        }
+    }
+
+    /// Also merge in the links files under `dir`; a code defined twice is
+    /// an error.
+    pub fn with_include_dir(mut self, dir: Option<String>) -> Self {
+        self.include_dir = dir;
+        self
+    }
+}
+
+/// The links files under `dir`, recursively and in path order. Hidden
+/// entries are skipped (a mounted ConfigMap keeps the real files under
+/// `..data`, next to symlinks to them), as are files whose extension isn't
+/// a links format.
+fn include_files(dir: &Path) -> std::io::Result<Vec<(PathBuf, LinkFormat)>> {
+    let mut files = Vec::new();
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        // Follows symlinks, so ConfigMap entries count as the files they are
+        if path.is_dir() {
+            files.extend(include_files(&path)?);
+        } else if let Some(format) = LinkFormat::from_path(&path.to_string_lossy()) {
+            files.push((path, format));
+        }
+    }
+    Ok(files)
+}
+
+impl LinkSource for FileSource {
+    fn describe(&self) -> String {
+        format!("file {}", self.path)
+    }
+
+    fn current(&self) -> BoxFuture<'_, Result<LinkSnapshot, Error>> {
+        Box::pin(async move {
+            let content = tokio::fs::read_to_string(&self.path).await?;
+            let mut links = self.format.parse(&content)?;
+            if let Some(dir) = &self.include_dir {
+                let dir = PathBuf::from(dir);
+                let files = tokio::task::spawn_blocking(move || include_files(&dir))
+                    .await
+                    .map_err(|e| Error::Other(e.to_string()))??;
+                for (file, format) in files {
+                    let content = tokio::fs::read_to_string(&file).await?;
+                    for (code, url) in format.parse(&content)? {
+                        if links.insert(code.clone(), url).is_some() {
+                            return Err(Error::Config(format!(
+                                "code '{}' in {} is already defined",
+                                code,
+                                file.display()
+                            )));
+                        }
+                    }
+                }
+            }
+            Ok(LinkSnapshot {
+                links,
+                revision: None,
+            })
+        })
+    }
+}
+
+/// A links file read straight out of a git ref's tree; see the `git` module.
+pub struct GitSource {
+    git_binary: String,
+    git: GitConfig,
+    format: LinkFormat,
+}
+
+impl GitSource {
+    pub fn new(git: GitConfig, format: LinkFormat) -> Self {
+        GitSource {
+            git_binary: GIT_BINARY.to_string(),
+            git,
+            format,
+        }
+    }
+
+    /// Use a different git binary (tests stand in `/usr/bin/false` for a
+    /// failing fetch).
+    #[cfg(test)]
+    pub fn with_git_binary(mut self, git_binary: &str) -> Self {
+        self.git_binary = git_binary.to_string();
+        self
+    }
+}
+
+impl LinkSource for GitSource {
+    fn describe(&self) -> String {
+        format!("git {}:{}", self.git.git_ref, self.git.links_path)
+    }
+
+    /// Read the links file at the configured ref, enforcing the signing
+    /// policy on its commit.
+    fn current(&self) -> BoxFuture<'_, Result<LinkSnapshot, Error>> {
+        Box::pin(async move {
+            let (commit, content) = git::read_links_at_ref(&self.git_binary, &self.git)?;
+            let links = self.format.parse(&content).map_err(|e| match e {
+                Error::Config(msg) => {
+                    Error::Config(format!("invalid links at commit {}: {}", commit, msg))
+                }
+                other => other,
+            })?;
+            Ok(LinkSnapshot {
+                links,
+                revision: Some(commit),
+            })
+        })
+    }
+
+    /// `git fetch` the links remote, then read the (possibly moved) ref.
+    /// Nothing is checked out, so there is no working tree to dirty and no
+    /// half-written file to read.
+    fn refresh(&self) -> BoxFuture<'_, Result<LinkSnapshot, Error>> {
+        Box::pin(async move {
+            git::fetch(&self.git_binary, &self.git)?;
+            self.current().await
+        })
+    }
+}
+
+/// A `code`/`url` table in a SQLite database, read in full on each load.
+/// Whatever writes the database should do so in transactions; a reader only
+/// ever sees committed rows.
+pub struct SqliteSource {
+    path: String,
+    table: String,
+}
+
+impl SqliteSource {
+    pub fn new(sqlite: &SqliteConfig) -> Self {
+        SqliteSource {
+            path: sqlite.path.clone(),
+            table: sqlite.table.clone(),
+        }
+    }
+}
+
+/// Read every row of `table`, rejecting duplicate codes (the table need not
+/// declare `code` unique).
+fn read_sqlite_table(path: &str, table: &str) -> Result<HashMap<String, String>, Error> {
+    let conn = rusqlite::Connection::open_with_flags(
+        path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let quoted = format!("\"{}\"", table.replace('"', "\"\""));
+    let mut stmt = conn.prepare(&format!("SELECT code, url FROM {}", quoted))?;
+    let mut rows = stmt.query([])?;
+    let mut links = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let code: String = row.get(0)?;
+        let url: String = row.get(1)?;
+        if links.contains_key(&code) {
+            return Err(Error::Config(format!(
+                "table {}: duplicate code '{}'",
+                table, code
+            )));
+        }
+        links.insert(code, url);
+    }
+    Ok(links)
+}
+
+impl LinkSource for SqliteSource {
+    fn describe(&self) -> String {
+        format!("sqlite {}:{}", self.path, self.table)
+    }
+
+    fn current(&self) -> BoxFuture<'_, Result<LinkSnapshot, Error>> {
+        Box::pin(async move {
+            let (path, table) = (self.path.clone(), self.table.clone());
+            let links = tokio::task::spawn_blocking(move || read_sqlite_table(&path, &table))
+                .await
+                .map_err(|e| Error::Other(format!("sqlite read task failed: {}", e)))??;
+            Ok(LinkSnapshot {
+                links: validate_links(links)?,
+                revision: None,
+            })
+        })
+    }
+}
+
+/// `ETag` / `Last-Modified` from a response, sent back to revalidate it.
+struct Validators {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+}
+
+/// The last table an `HttpSource` fetched, with its validators.
+struct CachedDocument {
+    validators: Validators,
+    links: HashMap<String, String>,
+}
+
+/// A links file served over HTTP(S), e.g. by an internal artifact server.
+/// Fetches are conditional (`If-None-Match` / `If-Modified-Since`), so an
+/// unchanged document costs a 304 and is neither re-parsed nor swapped in.
+pub struct HttpSource {
+    url: String,
+    format: LinkFormat,
+    timeout: Duration,
+    client: HttpsClient,
+    cached: Mutex<Option<CachedDocument>>,
+}
+
+impl HttpSource {
+    pub fn new(remote: &RemoteConfig, format: LinkFormat) -> Self {
+        HttpSource {
+            url: remote.url.clone().unwrap_or_default(),
+            format,
+            timeout: Duration::from_secs(remote.timeout_secs),
+            client: https_client(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// GET the document, conditionally if we have a previous copy. Returns
+    /// the response status, validators and body.
+    async fn fetch(&self) -> Result<(StatusCode, Validators, Vec<u8>), Error> {
+        if self.url.is_empty() {
+            return Err(Error::Config(
+                "remote.url must be set in http reload mode".to_string(),
+            ));
+        }
+        let mut request = hyper::Request::get(&self.url);
+        if let Some(cached) = self.cached.lock().unwrap().as_ref() {
+            if let Some(etag) = &cached.validators.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(modified) = &cached.validators.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, modified);
+            }
+        }
+        let request = request
+            .body(hyper::Body::empty())
+            .map_err(|e| Error::Config(format!("invalid remote.url {}: {}", self.url, e)))?;
+        let fetch = async {
+            let response = self.client.request(request).await?;
+            let status = response.status();
+            let validators = Validators {
+                etag: response.headers().get(header::ETAG).cloned(),
+                last_modified: response.headers().get(header::LAST_MODIFIED).cloned(),
+            };
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            Ok::<_, hyper::Error>((status, validators, body.to_vec()))
+        };
+        match tokio::time::timeout(self.timeout, fetch).await {
+            Ok(Ok(fetched)) => Ok(fetched),
+            Ok(Err(e)) => Err(Error::Remote(format!("GET {} failed: {}", self.url, e))),
+            Err(_) => Err(Error::Remote(format!(
+                "GET {} timed out after {}s",
+                self.url,
+                self.timeout.as_secs()
+            ))),
+        }
+    }
+}
+
+impl LinkSource for HttpSource {
+    fn describe(&self) -> String {
+        format!("http {}", self.url)
+    }
+
+    fn current(&self) -> BoxFuture<'_, Result<LinkSnapshot, Error>> {
+        Box::pin(async move {
+            let (status, validators, body) = self.fetch().await?;
+            let mut cached = self.cached.lock().unwrap();
+            if status == StatusCode::NOT_MODIFIED
+                && let Some(doc) = cached.as_ref()
+            {
+                return Ok(LinkSnapshot {
+                    links: doc.links.clone(),
+                    revision: None,
+                });
+            }
+            if !status.is_success() {
+                return Err(Error::Remote(format!(
+                    "GET {} returned {}",
+                    self.url, status
+                )));
+            }
+            let content = String::from_utf8(body)
+                .map_err(|_| Error::Config(format!("{} is not valid UTF-8", self.url)))?;
+            let links = self.format.parse(&content)?;
+            *cached = Some(CachedDocument {
+                validators,
+                links: links.clone(),
+            });
+            Ok(LinkSnapshot {
+                links,
+                revision: None,
+            })
+        })
+    }
+}
+
+/// Build the source `service` describes: the git ref's links file in git
+/// mode, the watched file in watch mode, the SQLite table in sqlite mode, or
+/// the remote file in http mode. File formats are `reload.format` if set,
+/// else inferred from the file's extension.
+pub fn from_config(service: &ServiceConfig) -> Arc<dyn LinkSource> {
+    let format = service.reload.format;
+    match service.reload.mode {
+        ReloadMode::Git => Arc::new(GitSource::new(
+            service.git.clone(),
+            LinkFormat::resolve(format, &service.git.links_path),
+        )),
+        ReloadMode::Watch => {
+            let path = &service.reload.watch_path;
+            Arc::new(
+                FileSource::new(path, LinkFormat::resolve(format, path))
+                    .with_include_dir(service.reload.include_dir.clone()),
+            )
+        }
+        ReloadMode::Sqlite => Arc::new(SqliteSource::new(&service.sqlite)),
+        ReloadMode::Http => {
+            let url = service.remote.url.as_deref().unwrap_or_default();
+            let path = url
+                .parse::<hyper::Uri>()
+                .map(|uri| uri.path().to_string())
+                .unwrap_or_default();
+            Arc::new(HttpSource::new(
+                &service.remote,
+                LinkFormat::resolve(format, &path),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected() -> HashMap<String, String> {
+        [("gh", "https://github.com"), ("docs", "https://docs.rs")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(LinkFormat::from_path("links.yml"), Some(LinkFormat::Yaml));
+        assert_eq!(
+            LinkFormat::from_path("a/links.JSON"),
+            Some(LinkFormat::Json)
+        );
+        assert_eq!(LinkFormat::from_path("links.toml"), Some(LinkFormat::Toml));
+        assert_eq!(LinkFormat::from_path("export.csv"), Some(LinkFormat::Csv));
+        assert_eq!(LinkFormat::from_path("links"), None);
+        assert_eq!(LinkFormat::resolve(None, "links"), LinkFormat::Yaml);
+        assert_eq!(
+            LinkFormat::resolve(Some(LinkFormat::Csv), "links.yaml"),
+            LinkFormat::Csv
+        );
+    }
+
+    #[test]
+    fn test_every_format_parses_the_same_table() {
+        let documents = [
+            (
+                LinkFormat::Yaml,
+                "# comment\ngh: https://github.com\ndocs: https://docs.rs\n",
+            ),
+            (
+                LinkFormat::Json,
+                r#"{"gh": "https://github.com", "docs": "https://docs.rs"}"#,
+            ),
+            (
+                LinkFormat::Toml,
+                "gh = \"https://github.com\"\ndocs = \"https://docs.rs\"\n",
+            ),
+            (
+                LinkFormat::Csv,
+                "code,url,owner\ngh,https://github.com,alice\n\n# retired\ndocs,https://docs.rs,bob\n",
+            ),
+        ];
+        for (format, content) in documents {
+            assert_eq!(format.parse(content).unwrap(), expected(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_every_format_rejects_empty_urls() {
+        let documents = [
+            (LinkFormat::Json, r#"{"gh": " "}"#),
+            (LinkFormat::Toml, "gh = \"\"\n"),
+            (LinkFormat::Csv, "gh,\n"),
+        ];
+        for (format, content) in documents {
+            let err = format.parse(content).unwrap_err();
+            assert!(
+                err.to_string().contains("Empty URL for key 'gh'"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_csv_rejects_duplicates_and_short_rows() {
+        let err = LinkFormat::Csv
+            .parse("gh,https://github.com\ngh,https://gitlab.com\n")
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("line 2: duplicate code 'gh'"),
+            "{}",
+            err
+        );
+        let err = LinkFormat::Csv.parse("gh\n").unwrap_err();
+        assert!(err.to_string().contains("expected code,url"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_file_source_reads_by_format() {
+        let path =
+            std::env::temp_dir().join(format!("redirective-src-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"gh": "https://github.com", "docs": "https://docs.rs"}"#,
+        )
+        .unwrap();
+        let path_str = path.to_string_lossy().into_owned();
+        let source = FileSource::new(&path_str, LinkFormat::resolve(None, &path_str));
+        let snapshot = source.refresh().await.unwrap();
+        assert_eq!(snapshot.links, expected());
+        assert_eq!(snapshot.revision, None);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_file_source_merges_include_dir() {
+        let dir = std::env::temp_dir().join(format!("redirective-include-{}", std::process::id()));
+        let include = dir.join("links.d");
+        std::fs::create_dir_all(include.join("team")).unwrap();
+        std::fs::create_dir_all(include.join("..data")).unwrap();
+        let path = dir.join("links.yaml");
+        std::fs::write(&path, "gh: https://github.com\n").unwrap();
+        std::fs::write(include.join("team/docs.csv"), "docs,https://docs.rs\n").unwrap();
+        std::fs::write(include.join("README.md"), "not links").unwrap();
+        std::fs::write(include.join("..data/gh.yaml"), "gh: https://hidden\n").unwrap();
+        let source = FileSource::new(&path.to_string_lossy(), LinkFormat::Yaml)
+            .with_include_dir(Some(include.to_string_lossy().into_owned()));
+        assert_eq!(source.current().await.unwrap().links, expected());
+
+        // A code defined twice is refused rather than silently overridden
+        std::fs::write(include.join("dup.json"), r#"{"gh": "https://gitlab.com"}"#).unwrap();
+        let err = source.current().await.unwrap_err();
+        assert!(err.to_string().contains("'gh'"), "{}", err);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn sqlite_db(name: &str) -> (std::path::PathBuf, rusqlite::Connection) {
+        let path =
+            std::env::temp_dir().join(format!("redirective-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch("CREATE TABLE links (code TEXT, url TEXT, owner TEXT);")
+            .unwrap();
+        (path, conn)
+    }
+
+    fn sqlite_source(path: &std::path::Path) -> SqliteSource {
+        SqliteSource::new(&SqliteConfig {
+            path: path.to_string_lossy().into_owned(),
+            ..SqliteConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_source_reads_table() {
+        let (path, conn) = sqlite_db("sqlite-read");
+        conn.execute_batch(
+            "INSERT INTO links VALUES ('gh', 'https://github.com', 'alice');
+             INSERT INTO links VALUES ('docs', 'https://docs.rs', 'bob');",
+        )
+        .unwrap();
+        let source = sqlite_source(&path);
+        let snapshot = source.current().await.unwrap();
+        assert_eq!(snapshot.links, expected());
+        assert_eq!(snapshot.revision, None);
+
+        // Committed writes show up on the next load.
+        conn.execute("DELETE FROM links WHERE code = 'docs'", [])
+            .unwrap();
+        let snapshot = source.refresh().await.unwrap();
+        assert_eq!(snapshot.links.len(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_source_validates_rows() {
+        let (path, conn) = sqlite_db("sqlite-invalid");
+        conn.execute_batch("INSERT INTO links VALUES ('gh', '  ', NULL);")
+            .unwrap();
+        let source = sqlite_source(&path);
+        let err = source.current().await.unwrap_err();
+        assert!(
+            err.to_string().contains("Empty URL for key 'gh'"),
+            "{}",
+            err
+        );
+
+        conn.execute_batch(
+            "DELETE FROM links;
+             INSERT INTO links VALUES ('gh', 'https://github.com', NULL);
+             INSERT INTO links VALUES ('gh', 'https://gitlab.com', NULL);",
+        )
+        .unwrap();
+        let err = source.current().await.unwrap_err();
+        assert!(err.to_string().contains("duplicate code 'gh'"), "{}", err);
+
+        let missing = SqliteSource::new(&SqliteConfig {
+            path: path.to_string_lossy().into_owned(),
+            table: "nope".to_string(),
+            ..SqliteConfig::default()
+        });
+        assert!(missing.current().await.is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Serve `/links.json` with ETag `"v<version>"`, answering 304 to a
+    /// matching `If-None-Match`. Returns the URL, the version to bump, and
+    /// the `If-None-Match` header of each request (`None` when absent).
+    async fn spawn_mock_origin() -> (String, Arc<Mutex<u32>>, Arc<Mutex<Vec<Option<String>>>>) {
+        use axum::Router;
+        use axum::http::HeaderMap;
+        use axum::response::IntoResponse;
+        use axum::routing::get;
+
+        let version = Arc::new(Mutex::new(1u32));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let (v, log) = (version.clone(), seen.clone());
+        let app = Router::new()
+            .route(
+                "/links.json",
+                get(move |headers: HeaderMap| {
+                    let (v, log) = (v.clone(), log.clone());
+                    async move {
+                        let if_none_match = headers
+                            .get(header::IF_NONE_MATCH)
+                            .map(|h| h.to_str().unwrap().to_string());
+                        log.lock().unwrap().push(if_none_match.clone());
+                        let version = *v.lock().unwrap();
+                        let etag = format!("\"v{}\"", version);
+                        if if_none_match.as_deref() == Some(etag.as_str()) {
+                            return StatusCode::NOT_MODIFIED.into_response();
+                        }
+                        let body = if version == 1 {
+                            r#"{"gh": "https://github.com", "docs": "https://docs.rs"}"#
+                        } else {
+                            r#"{"gh": "https://gitlab.com"}"#
+                        };
+                        ([(header::ETAG, etag)], body).into_response()
+                    }
+                }),
+            )
+            .route(
+                "/slow.json",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "{}"
+                }),
+            );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        (format!("http://{}", addr), version, seen)
+    }
+
+    fn http_source(url: String, timeout_secs: u64) -> HttpSource {
+        let remote = RemoteConfig {
+            url: Some(url),
+            timeout_secs,
+        };
+        HttpSource::new(&remote, LinkFormat::Json)
+    }
+
+    #[tokio::test]
+    async fn test_http_source_revalidates_with_etag() {
+        let (base, version, seen) = spawn_mock_origin().await;
+        let source = http_source(format!("{}/links.json", base), 5);
+        assert_eq!(source.current().await.unwrap().links, expected());
+        // Unchanged: a conditional request, answered 304 from our copy.
+        assert_eq!(source.refresh().await.unwrap().links, expected());
+        *version.lock().unwrap() = 2;
+        let links = source.refresh().await.unwrap().links;
+        assert_eq!(
+            links.get("gh").map(String::as_str),
+            Some("https://gitlab.com")
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [None, Some("\"v1\"".to_string()), Some("\"v1\"".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_source_errors() {
+        let (base, _, _) = spawn_mock_origin().await;
+        let missing = http_source(format!("{}/nope.json", base), 5);
+        let err = missing.current().await.unwrap_err();
+        assert!(matches!(err, Error::Remote(_)), "{}", err);
+        assert!(err.to_string().contains("404"), "{}", err);
+
+        let slow = http_source(format!("{}/slow.json", base), 1);
+        let err = slow.current().await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+
+        let unset = HttpSource::new(&RemoteConfig::default(), LinkFormat::Json);
+        assert!(matches!(unset.current().await, Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,469 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! tls module: built-in HTTPS termination with rustls. The certificate is
+//! reloaded when its files change on disk, so a renewal needs no restart, and
+//! the plain HTTP listener can be turned into a redirect to HTTPS.
+
+use crate::config::TlsConfig;
+use crate::errors::Error;
+use crate::reload::file_stamps;
+use arc_swap::ArcSwap;
+use axum::{
+    Router,
+    extract::connect_info::Connected,
+    http::{HeaderMap, StatusCode, Uri, header, uri::Authority},
+    response::{IntoResponse, Response},
+};
+use hyper::server::accept::Accept;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::{
+    ServerConfig, SupportedProtocolVersion,
+    crypto::ring,
+    version::{TLS12, TLS13},
+};
+use tokio_rustls::server::TlsStream;
+
+/// How long a client gets to finish the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The certificate the HTTPS listener presents, swapped in place when its
+/// files change. Clones share the current certificate.
+#[derive(Clone)]
+pub struct CertStore {
+    config: Arc<ArcSwap<ServerConfig>>,
+    cert_path: String,
+    key_path: String,
+    tls13_only: bool,
+}
+
+impl CertStore {
+    /// Load the PEM certificate chain and key at `cert_path`/`key_path`.
+    pub fn load(cert_path: &str, key_path: &str, tls13_only: bool) -> Result<Self, Error> {
+        let config = server_config(cert_path, key_path, tls13_only)?;
+        Ok(CertStore {
+            config: Arc::new(ArcSwap::from_pointee(config)),
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            tls13_only,
+        })
+    }
+
+    /// The store `tls` configures: `None` if TLS is off, an error if it is
+    /// only partly configured or the certificate doesn't load.
+    pub fn from_config(tls: &TlsConfig) -> Result<Option<Self>, Error> {
+        match (&tls.address, &tls.cert_path, &tls.key_path) {
+            (None, None, None) => Ok(None),
+            (Some(_), Some(cert_path), Some(key_path)) => {
+                CertStore::load(cert_path, key_path, tls.tls13_only).map(Some)
+            }
+            _ => Err(Error::Config(
+                "[tls] needs address, cert_path and key_path together".to_string(),
+            )),
+        }
+    }
+
+    /// The configuration new connections are accepted with.
+    pub fn current(&self) -> Arc<ServerConfig> {
+        self.config.load_full()
+    }
+
+    /// Re-read the certificate and key. On failure the current pair stays in
+    /// use.
+    pub fn reload(&self) -> Result<(), Error> {
+        let config = server_config(&self.cert_path, &self.key_path, self.tls13_only)?;
+        self.config.store(Arc::new(config));
+        Ok(())
+    }
+}
+
+/// Build a rustls server configuration from PEM files.
+fn server_config(cert_path: &str, key_path: &str, tls13_only: bool) -> Result<ServerConfig, Error> {
+    let read_error = |path: &str, e: std::io::Error| Error::Tls(format!("{}: {}", path, e));
+    let mut certs = BufReader::new(File::open(cert_path).map_err(|e| read_error(cert_path, e))?);
+    let certs = rustls_pemfile::certs(&mut certs)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| read_error(cert_path, e))?;
+    if certs.is_empty() {
+        return Err(Error::Tls(format!("{}: no certificates found", cert_path)));
+    }
+    let mut key = BufReader::new(File::open(key_path).map_err(|e| read_error(key_path, e))?);
+    let key = rustls_pemfile::private_key(&mut key)
+        .map_err(|e| read_error(key_path, e))?
+        .ok_or_else(|| Error::Tls(format!("{}: no private key found", key_path)))?;
+    let versions: &[&SupportedProtocolVersion] = if tls13_only {
+        &[&TLS13]
+    } else {
+        &[&TLS13, &TLS12]
+    };
+    let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_protocol_versions(versions)
+        .map_err(|e| Error::Tls(e.to_string()))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| Error::Tls(format!("{}: {}", cert_path, e)))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Check the certificate and key files every `interval` and reload them
+/// when either has changed. A pair that fails to load (e.g. caught between
+/// writing the certificate and the key) is retried at the next check.
+pub fn spawn_reload_task(store: CertStore, interval: Duration) {
+    if interval.is_zero() {
+        tracing::info!("TLS certificate reload disabled (reload_check_secs = 0)");
+        return;
+    }
+    let paths = vec![store.cert_path.clone(), store.key_path.clone()];
+    task::spawn(async move {
+        let mut last = file_stamps(&paths);
+        loop {
+            tokio::time::sleep(interval).await;
+            let stamps = file_stamps(&paths);
+            if stamps == last {
+                continue;
+            }
+            match store.reload() {
+                Ok(()) => {
+                    tracing::info!(cert = %store.cert_path, "TLS certificate reloaded");
+                    last = stamps;
+                }
+                Err(e) => tracing::warn!(
+                    error = %e,
+                    "TLS certificate reload failed; keeping the current certificate"
+                ),
+            }
+        }
+    });
+}
+
+/// An accepted TLS connection and the client's address.
+pub struct TlsConn {
+    stream: TlsStream<TcpStream>,
+    remote: SocketAddr,
+}
+
+impl AsyncRead for TlsConn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConn {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Lets handlers see the client address (`ConnectInfo`) over TLS too.
+impl Connected<&TlsConn> for SocketAddr {
+    fn connect_info(target: &TlsConn) -> Self {
+        target.remote
+    }
+}
+
+/// Connections that completed the TLS handshake, for `axum::Server::builder`.
+pub struct TlsIncoming {
+    conns: mpsc::Receiver<TlsConn>,
+}
+
+impl Accept for TlsIncoming {
+    type Conn = TlsConn;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.conns.poll_recv(cx).map(|conn| conn.map(Ok))
+    }
+}
+
+/// Accept connections on `listener` and run their handshakes with the
+/// store's current certificate. Handshakes run concurrently, so a slow or
+/// stalled client doesn't hold up the others; accepting stops once the
+/// returned `TlsIncoming` is dropped.
+pub fn incoming(listener: TcpListener, store: CertStore) -> TlsIncoming {
+    let (tx, conns) = mpsc::channel(64);
+    task::spawn(async move {
+        loop {
+            let (tcp, remote) = tokio::select! {
+                _ = tx.closed() => return,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // e.g. out of file descriptors; don't spin
+                        tracing::warn!(error = %e, "TLS listener accept failed");
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                },
+            };
+            let acceptor = TlsAcceptor::from(store.current());
+            let tx = tx.clone();
+            task::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = tx.send(TlsConn { stream, remote }).await;
+                    }
+                    Ok(Err(e)) => tracing::debug!(%remote, error = %e, "TLS handshake failed"),
+                    Err(_) => tracing::debug!(%remote, "TLS handshake timed out"),
+                }
+            });
+        }
+    });
+    TlsIncoming { conns }
+}
+
+/// An app answering every request with a 308 redirect to the same host and
+/// path on the HTTPS listener's `https_port`.
+pub fn redirect_router(https_port: u16) -> Router<()> {
+    Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        redirect_to_https(&headers, &uri, https_port)
+    })
+}
+
+fn redirect_to_https(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Response {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok());
+    let Some(host) = host else {
+        return (StatusCode::BAD_REQUEST, "missing Host header").into_response();
+    };
+    let port = if https_port == 443 {
+        String::new()
+    } else {
+        format!(":{}", https_port)
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let location = format!("https://{}{}{}", host.host(), port, path);
+    (
+        StatusCode::PERMANENT_REDIRECT,
+        [(header::LOCATION, location)],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+pub mod testing {
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::net::TcpStream;
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::client::TlsStream;
+    use tokio_rustls::rustls::{
+        ClientConfig, RootCertStore, SupportedProtocolVersion, crypto::ring, pki_types::ServerName,
+    };
+
+    /// Connect to `addr` as "localhost", trusting the certificates in
+    /// `cert_path`, offering `alpn` and limited to `versions`.
+    pub async fn connect(
+        addr: SocketAddr,
+        cert_path: &str,
+        alpn: &[&[u8]],
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> std::io::Result<TlsStream<TcpStream>> {
+        let mut roots = RootCertStore::empty();
+        let mut pem = BufReader::new(File::open(cert_path)?);
+        for cert in rustls_pemfile::certs(&mut pem) {
+            roots.add(cert?).unwrap();
+        }
+        let mut config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_protocol_versions(versions)
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|proto| proto.to_vec()).collect();
+        let tcp = TcpStream::connect(addr).await?;
+        let name = ServerName::try_from("localhost").unwrap();
+        TlsConnector::from(Arc::new(config))
+            .connect(name, tcp)
+            .await
+    }
+
+    /// A directory holding a fresh self-signed certificate for "localhost".
+    /// Removed on drop.
+    pub struct CertFiles {
+        dir: std::path::PathBuf,
+        pub cert_path: String,
+        pub key_path: String,
+    }
+
+    impl CertFiles {
+        pub fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "redirective-tls-{}-{}",
+                std::process::id(),
+                fastrand::u64(..)
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            let files = CertFiles {
+                cert_path: dir.join("cert.pem").to_string_lossy().into_owned(),
+                key_path: dir.join("key.pem").to_string_lossy().into_owned(),
+                dir,
+            };
+            files.renew();
+            files
+        }
+
+        /// Replace the pair with a new certificate, returning its DER.
+        pub fn renew(&self) -> Vec<u8> {
+            let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+            std::fs::write(&self.cert_path, cert.cert.pem()).unwrap();
+            std::fs::write(&self.key_path, cert.key_pair.serialize_pem()).unwrap();
+            cert.cert.der().to_vec()
+        }
+    }
+
+    impl Drop for CertFiles {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{CertFiles, connect};
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tower::ServiceExt;
+
+    /// Serve a one-route app over TLS with `store`, returning its address.
+    async fn spawn_tls_server(store: CertStore) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/", axum::routing::get(|| async { "hello" }));
+        tokio::spawn(
+            axum::Server::builder(incoming(listener, store)).serve(app.into_make_service()),
+        );
+        addr
+    }
+
+    /// `GET /` over HTTP/1.1, limited to `versions`; returns the
+    /// certificate the server presented and the raw response.
+    async fn fetch(
+        addr: SocketAddr,
+        cert_path: &str,
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> std::io::Result<(Vec<u8>, String)> {
+        let mut tls = connect(addr, cert_path, &[b"http/1.1"], versions).await?;
+        let presented = tls.get_ref().1.peer_certificates().unwrap()[0].to_vec();
+        tls.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        tls.read_to_string(&mut response).await?;
+        Ok((presented, response))
+    }
+
+    #[tokio::test]
+    async fn test_serves_over_tls_and_picks_up_renewed_certificate() {
+        let files = CertFiles::new();
+        let store = CertStore::load(&files.cert_path, &files.key_path, false).unwrap();
+        let addr = spawn_tls_server(store.clone()).await;
+        let (presented, response) = fetch(addr, &files.cert_path, &[&TLS13]).await.unwrap();
+        assert!(response.ends_with("hello"), "{}", response);
+
+        let renewed = files.renew();
+        assert_ne!(presented, renewed);
+        store.reload().unwrap();
+        let (presented, _) = fetch(addr, &files.cert_path, &[&TLS13]).await.unwrap();
+        assert_eq!(presented, renewed);
+    }
+
+    #[tokio::test]
+    async fn test_tls13_only_refuses_tls12() {
+        let files = CertFiles::new();
+        let store = CertStore::load(&files.cert_path, &files.key_path, true).unwrap();
+        let addr = spawn_tls_server(store).await;
+        assert!(fetch(addr, &files.cert_path, &[&TLS12]).await.is_err());
+        assert!(fetch(addr, &files.cert_path, &[&TLS13]).await.is_ok());
+
+        let store = CertStore::load(&files.cert_path, &files.key_path, false).unwrap();
+        let addr = spawn_tls_server(store).await;
+        assert!(fetch(addr, &files.cert_path, &[&TLS12]).await.is_ok());
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_current_certificate() {
+        let files = CertFiles::new();
+        let store = CertStore::load(&files.cert_path, &files.key_path, false).unwrap();
+        let before = store.current();
+        std::fs::write(&files.key_path, "not a key").unwrap();
+        assert!(store.reload().is_err());
+        assert!(Arc::ptr_eq(&before, &store.current()));
+    }
+
+    #[test]
+    fn test_from_config_needs_every_setting() {
+        let files = CertFiles::new();
+        assert!(
+            CertStore::from_config(&TlsConfig::default())
+                .unwrap()
+                .is_none()
+        );
+        let mut tls = TlsConfig {
+            address: Some("127.0.0.1:8443".to_string()),
+            cert_path: Some(files.cert_path.clone()),
+            ..TlsConfig::default()
+        };
+        assert!(CertStore::from_config(&tls).is_err());
+        tls.key_path = Some(files.key_path.clone());
+        assert!(CertStore::from_config(&tls).unwrap().is_some());
+    }
+
+    async fn redirect(port: u16, host: Option<&str>, uri: &str) -> Response {
+        let mut request = Request::get(uri);
+        if let Some(host) = host {
+            request = request.header(header::HOST, host);
+        }
+        redirect_router(port)
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_redirect_keeps_host_path_and_query() {
+        let response = redirect(8443, Some("jrj.org:8080"), "/gh?x=1").await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://jrj.org:8443/gh?x=1"
+        );
+        let response = redirect(443, Some("jrj.org"), "/gh").await;
+        assert_eq!(response.headers()[header::LOCATION], "https://jrj.org/gh");
+        let response = redirect(443, None, "/gh").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
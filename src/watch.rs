@@ -0,0 +1,157 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! watch module: debounced filesystem watching of the links file (and its
+//! include directory), for deployments that mount links.yaml (e.g. from a
+//! ConfigMap) instead of using git.
+
+use crate::errors::Error;
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Watches a links file and its include directory and yields one
+/// notification per settled burst of changes to them.
+pub struct LinksWatcher {
+    // Dropping the watcher stops event delivery, so it lives as long as we do.
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<()>,
+    debounce: Duration,
+}
+
+/// Returns true if `event` may have changed the watched file, or anything
+/// under `include_dir`.
+///
+/// The parent directory is watched rather than the file itself: editors and
+/// sync tools replace files by rename, and Kubernetes swaps a ConfigMap by
+/// re-pointing the directory's `..data` symlink, neither of which a watch on
+/// the old inode would see.
+fn is_relevant(event: &Event, file_name: &OsString, include_dir: Option<&Path>) -> bool {
+    if let EventKind::Access(kind) = event.kind
+        && kind != AccessKind::Close(AccessMode::Write)
+    {
+        return false;
+    }
+    event.paths.iter().any(|p| {
+        include_dir.is_some_and(|dir| p.starts_with(dir))
+            || p.file_name()
+                .is_some_and(|name| name == file_name || name.to_string_lossy().starts_with(".."))
+    })
+}
+
+impl LinksWatcher {
+    /// Start watching `path` (via its parent directory) and, recursively,
+    /// `include_dir`, which must exist. Bursts of events are collapsed until
+    /// `debounce` passes without a further event.
+    pub fn new(path: &Path, include_dir: Option<&Path>, debounce: Duration) -> Result<Self, Error> {
+        let file_name = path
+            .file_name()
+            .map(OsString::from)
+            .ok_or_else(|| Error::Config(format!("watch path {:?} has no file name", path)))?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let include: Option<PathBuf> = include_dir.map(Path::to_path_buf);
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) if is_relevant(&event, &file_name, include.as_deref()) => {
+                    let _ = tx.send(());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "links file watch error"),
+            })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        if let Some(include_dir) = include_dir {
+            watcher.watch(include_dir, RecursiveMode::Recursive)?;
+        }
+        Ok(LinksWatcher {
+            _watcher: watcher,
+            events,
+            debounce,
+        })
+    }
+
+    /// Wait for the next settled change. Returns `false` if the watcher has
+    /// shut down and no further changes will be reported.
+    pub async fn changed(&mut self) -> bool {
+        if self.events.recv().await.is_none() {
+            return false;
+        }
+        loop {
+            match tokio::time::timeout(self.debounce, self.events.recv()).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return false,
+                Err(_) => return true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind};
+    use std::path::PathBuf;
+
+    fn event(kind: EventKind, path: &str) -> Event {
+        Event::new(kind).add_path(PathBuf::from(path))
+    }
+
+    #[test]
+    fn test_is_relevant_filters_by_file_name() {
+        let name = OsString::from("links.yaml");
+        let modify = EventKind::Modify(ModifyKind::Any);
+        assert!(is_relevant(&event(modify, "/cfg/links.yaml"), &name, None));
+        assert!(!is_relevant(&event(modify, "/cfg/other.yaml"), &name, None));
+    }
+
+    #[test]
+    fn test_is_relevant_sees_configmap_symlink_swap() {
+        let name = OsString::from("links.yaml");
+        let create = EventKind::Create(CreateKind::Any);
+        assert!(is_relevant(&event(create, "/cfg/..data"), &name, None));
+    }
+
+    #[test]
+    fn test_is_relevant_sees_anything_under_include_dir() {
+        let name = OsString::from("links.yaml");
+        let include = Path::new("/cfg/links.d");
+        let modify = EventKind::Modify(ModifyKind::Any);
+        let nested = event(modify, "/cfg/links.d/team/more.csv");
+        assert!(is_relevant(&nested, &name, Some(include)));
+        assert!(!is_relevant(&nested, &name, None));
+        assert!(!is_relevant(
+            &event(modify, "/cfg/other.yaml"),
+            &name,
+            Some(include)
+        ));
+    }
+
+    #[test]
+    fn test_is_relevant_ignores_reads() {
+        let name = OsString::from("links.yaml");
+        let read = EventKind::Access(AccessKind::Close(AccessMode::Read));
+        assert!(!is_relevant(&event(read, "/cfg/links.yaml"), &name, None));
+    }
+
+    #[tokio::test]
+    async fn test_burst_of_writes_collapses_into_one_change() {
+        let dir = std::env::temp_dir().join(format!("redirective-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("links.yaml");
+        std::fs::write(&path, "a: https://a\n").unwrap();
+        let mut watcher = LinksWatcher::new(&path, None, Duration::from_millis(200)).unwrap();
+        for i in 0..5 {
+            std::fs::write(&path, format!("a: https://a/{}\n", i)).unwrap();
+        }
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed()).await;
+        assert_eq!(changed, Ok(true));
+        // The whole burst was consumed by the first notification.
+        let again = tokio::time::timeout(Duration::from_millis(400), watcher.changed()).await;
+        assert!(again.is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,381 @@
+// (C) Copyright 2025, Joseph R. Jones - https://jrj.org - Licensed under MIT License
+//! yaml_edit module: edits links.yaml line by line, so comments, blank lines
+//! and key order survive programmatic changes (a serde_yaml round trip would
+//! drop them).
+//!
+//! Only the layout links.yaml actually uses is supported: a top-level block
+//! mapping of `code: url` entries, each on one line (indented continuation
+//! lines of a wrapped value are kept with their entry), with comments and
+//! blank lines anywhere. `LinksDocument::parse` rejects anything else rather
+//! than risk a wrong edit.
+
+use crate::errors::Error;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A top-level `code: url` entry found on a line.
+struct Entry {
+    /// The code, with any quoting removed.
+    key: String,
+    /// Byte offset in the line just past the `:` that ends the key.
+    value_start: usize,
+}
+
+/// Parse the key of a top-level mapping entry. Indented lines, comments,
+/// document markers and anything else that isn't `key: value` give `None`.
+fn parse_entry(line: &str) -> Option<Entry> {
+    let first = line.chars().next()?;
+    if first.is_whitespace() || first == '#' || is_document_marker(line) {
+        return None;
+    }
+    let (key, rest_start) = match first {
+        '"' | '\'' => {
+            let end = closing_quote(line, first)?;
+            let key: String = serde_yaml::from_str(&line[..=end]).ok()?;
+            (key, end + 1)
+        }
+        '{' | '[' | '-' | '?' | '&' | '*' | '!' | '|' | '>' => return None,
+        _ => {
+            let colon = line
+                .char_indices()
+                .find(|&(i, c)| {
+                    c == ':' && line[i + 1..].chars().next().is_none_or(char::is_whitespace)
+                })
+                .map(|(i, _)| i)?;
+            (line[..colon].trim_end().to_string(), colon)
+        }
+    };
+    let after = &line[rest_start..];
+    let colon = rest_start + after.len() - after.trim_start().len();
+    if !line[colon..].starts_with(':') {
+        return None;
+    }
+    Some(Entry {
+        key,
+        value_start: colon + 1,
+    })
+}
+
+fn is_document_marker(line: &str) -> bool {
+    ["---", "..."].iter().any(|m| {
+        line.strip_prefix(m)
+            .is_some_and(|rest| rest.trim().is_empty())
+    })
+}
+
+/// Index of the quote closing the quoted scalar that opens `text`.
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => {
+                chars.next();
+            }
+            c if c == quote => {
+                // '' is an escaped quote inside a single-quoted scalar.
+                if quote == '\'' && text[i + 1..].starts_with('\'') {
+                    chars.next();
+                } else {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The trailing comment of an entry's value part (including the whitespace
+/// before `#`), or `""` if there is none.
+fn trailing_comment(value: &str) -> &str {
+    let trimmed = value.trim_start();
+    let mut from = value.len() - trimmed.len();
+    if let Some(quote @ ('"' | '\'')) = trimmed.chars().next() {
+        match closing_quote(trimmed, quote) {
+            Some(end) => from += end + 1,
+            None => return "",
+        }
+    }
+    // A `#` only starts a comment after whitespace (`a#b` is part of a URL).
+    let hash = value[from..]
+        .char_indices()
+        .map(|(i, c)| (from + i, c))
+        .find(|&(i, c)| c == '#' && value[..i].ends_with(char::is_whitespace));
+    match hash {
+        Some((i, _)) => &value[value[..i].trim_end().len()..],
+        None => "",
+    }
+}
+
+/// Render `s` as a YAML scalar: plain when it reads back as the same string
+/// (not, say, a bool or a number), otherwise double-quoted.
+pub fn scalar(s: &str) -> String {
+    let plain = !s.is_empty()
+        && !s.contains('\n')
+        && serde_yaml::from_str::<HashMap<String, Value>>(&format!("k: {}", s))
+            .is_ok_and(|m| m.get("k") == Some(&Value::String(s.to_string())));
+    if plain {
+        s.to_string()
+    } else {
+        // A JSON string is a valid YAML double-quoted scalar.
+        serde_json::to_string(s).expect("strings always serialize")
+    }
+}
+
+/// Whether `line` continues the previous entry's value: indented, and not
+/// blank or a comment.
+fn is_continuation(line: &str) -> bool {
+    line.starts_with(char::is_whitespace)
+        && !line.trim().is_empty()
+        && !line.trim_start().starts_with('#')
+}
+
+/// links.yaml as lines, editable without disturbing anything but the entry
+/// being changed. `to_string()` gives the edited file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinksDocument {
+    lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+impl LinksDocument {
+    /// Split `content` into lines, checking every line is an entry, part of
+    /// one, a comment, blank or a document marker.
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let mut in_entry = false;
+        for (n, line) in lines.iter().enumerate() {
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') || is_document_marker(line) {
+                continue;
+            }
+            if parse_entry(line).is_some() {
+                in_entry = true;
+            } else if !(in_entry && is_continuation(line)) {
+                return Err(Error::Config(format!(
+                    "links.yaml line {}: not a `code: url` entry; cannot edit in place",
+                    n + 1
+                )));
+            }
+        }
+        Ok(LinksDocument {
+            lines,
+            trailing_newline: content.ends_with('\n') || content.is_empty(),
+        })
+    }
+
+    /// Line range of `code`'s entry, including continuation lines.
+    fn find(&self, code: &str) -> Option<(usize, usize)> {
+        let start = self
+            .lines
+            .iter()
+            .position(|l| parse_entry(l).is_some_and(|e| e.key == code))?;
+        Some((start, self.entry_end(start)))
+    }
+
+    /// End (exclusive) of the entry starting on line `start`.
+    fn entry_end(&self, start: usize) -> usize {
+        start
+            + 1
+            + self.lines[start + 1..]
+                .iter()
+                .take_while(|l| is_continuation(l))
+                .count()
+    }
+
+    pub fn contains(&self, code: &str) -> bool {
+        self.find(code).is_some()
+    }
+
+    /// Add a new entry after the last existing one, so comments trailing
+    /// the file stay last.
+    pub fn insert(&mut self, code: &str, url: &str) -> Result<(), Error> {
+        if self.contains(code) {
+            return Err(Error::Config(format!("code '{}' already exists", code)));
+        }
+        let at = (0..self.lines.len())
+            .rev()
+            .find(|&i| parse_entry(&self.lines[i]).is_some())
+            .map_or(self.lines.len(), |last| self.entry_end(last));
+        self.lines
+            .insert(at, format!("{}: {}", scalar(code), scalar(url)));
+        // Appending to a file without a final newline gives it one.
+        if at == self.lines.len() - 1 {
+            self.trailing_newline = true;
+        }
+        Ok(())
+    }
+
+    /// Point an existing entry at `url`, keeping its key as written and any
+    /// trailing comment.
+    pub fn update(&mut self, code: &str, url: &str) -> Result<(), Error> {
+        let (start, end) = self.find(code).ok_or_else(|| missing(code))?;
+        let line = &self.lines[start];
+        let entry = parse_entry(line).expect("found entries parse");
+        let comment = trailing_comment(&line[entry.value_start..]);
+        let rewritten = format!("{} {}{}", &line[..entry.value_start], scalar(url), comment);
+        self.lines.splice(start..end, [rewritten]);
+        Ok(())
+    }
+
+    /// Insert or update.
+    pub fn set(&mut self, code: &str, url: &str) -> Result<(), Error> {
+        if self.contains(code) {
+            self.update(code, url)
+        } else {
+            self.insert(code, url)
+        }
+    }
+
+    /// Change an entry's code in place, keeping its value and comments.
+    /// (The write API doesn't rename; this is for other editors of the file.)
+    #[allow(dead_code)]
+    pub fn rename(&mut self, code: &str, new_code: &str) -> Result<(), Error> {
+        let (start, _) = self.find(code).ok_or_else(|| missing(code))?;
+        if code != new_code && self.contains(new_code) {
+            return Err(Error::Config(format!("code '{}' already exists", new_code)));
+        }
+        let line = &self.lines[start];
+        let entry = parse_entry(line).expect("found entries parse");
+        self.lines[start] = format!("{}:{}", scalar(new_code), &line[entry.value_start..]);
+        Ok(())
+    }
+
+    /// Delete an entry, with its continuation lines.
+    pub fn remove(&mut self, code: &str) -> Result<(), Error> {
+        let (start, end) = self.find(code).ok_or_else(|| missing(code))?;
+        self.lines.drain(start..end);
+        Ok(())
+    }
+}
+
+fn missing(code: &str) -> Error {
+    Error::Config(format!("code '{}' does not exist", code))
+}
+
+impl fmt::Display for LinksDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            f.write_str(line)?;
+        }
+        if self.trailing_newline && !self.lines.is_empty() {
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_links;
+
+    /// Golden files: `testdata/yaml_edit/<case>.in.yaml` edited by the
+    /// case's operations must give `<case>.out.yaml` byte for byte.
+    macro_rules! golden {
+        ($case:literal) => {
+            (
+                include_str!(concat!("../testdata/yaml_edit/", $case, ".in.yaml")),
+                include_str!(concat!("../testdata/yaml_edit/", $case, ".out.yaml")),
+            )
+        };
+    }
+
+    fn edit(input: &str, ops: impl FnOnce(&mut LinksDocument) -> Result<(), Error>) -> String {
+        let mut doc = LinksDocument::parse(input).unwrap();
+        ops(&mut doc).unwrap();
+        let out = doc.to_string();
+        // Every edit still parses as a valid links file.
+        parse_links(&out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_golden_insert() {
+        let (input, expected) = golden!("insert");
+        let out = edit(input, |doc| {
+            doc.insert("new", "https://new.example")?;
+            doc.insert("needs quotes", "https://example.com/ #not-a-comment")?;
+            doc.insert("yes", "true")
+        });
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_golden_update() {
+        let (input, expected) = golden!("update");
+        let out = edit(input, |doc| {
+            doc.update("foo", "https://foo.example")?;
+            doc.update("quoted key", "https://quoted.example")?;
+            doc.update("wrapped", "https://wrapped.example")?;
+            doc.update("hash", "https://example.com/page#top")
+        });
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_golden_rename() {
+        let (input, expected) = golden!("rename");
+        let out = edit(input, |doc| {
+            doc.rename("foo", "foo2")?;
+            doc.rename("quoted key", "plain")?;
+            doc.rename("bar", "bar: baz")
+        });
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_golden_remove() {
+        let (input, expected) = golden!("remove");
+        let out = edit(input, |doc| {
+            doc.remove("wrapped")?;
+            doc.remove("bar")
+        });
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_untouched_document_round_trips() {
+        for (input, _) in [
+            golden!("insert"),
+            golden!("update"),
+            golden!("rename"),
+            golden!("remove"),
+        ] {
+            assert_eq!(LinksDocument::parse(input).unwrap().to_string(), input);
+        }
+    }
+
+    #[test]
+    fn test_conflicts_and_missing_codes_rejected() {
+        let mut doc = LinksDocument::parse("foo: https://a\nbar: https://b\n").unwrap();
+        assert!(doc.insert("foo", "https://c").is_err());
+        assert!(doc.update("nope", "https://c").is_err());
+        assert!(doc.rename("foo", "bar").is_err());
+        assert!(doc.remove("nope").is_err());
+        assert_eq!(doc.to_string(), "foo: https://a\nbar: https://b\n");
+    }
+
+    #[test]
+    fn test_unsupported_layouts_rejected() {
+        assert!(LinksDocument::parse("{foo: https://a}\n").is_err());
+        assert!(LinksDocument::parse("- foo\n").is_err());
+        assert!(LinksDocument::parse("  foo: https://a\n").is_err());
+        assert!(LinksDocument::parse("").is_ok());
+    }
+
+    #[test]
+    fn test_scalar_quotes_only_when_needed() {
+        assert_eq!(
+            scalar("https://example.com/a?b=c"),
+            "https://example.com/a?b=c"
+        );
+        assert_eq!(scalar("x #y"), "\"x #y\"");
+        assert_eq!(scalar("true"), "\"true\"");
+        assert_eq!(scalar("- x"), "\"- x\"");
+    }
+}